@@ -1,36 +1,13 @@
 use ippper::model::{PageOrientation, Resolution};
 use ippper::server::{serve_adaptive_https, tls_config_from_reader, wrap_as_http_service};
-use ippper::service::simple::{
-    PrinterInfoBuilder, SimpleIppDocument, SimpleIppService, SimpleIppServiceHandler,
-};
+use ippper::service::simple::{PrinterInfoBuilder, SimpleIppDocument, SimpleIppService};
+use ippper::service::WriteSink;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
 use tokio::fs::File;
-use tokio::io;
 use tokio_util::compat::*;
 use uuid::Uuid;
 
-struct MyHandler {}
-impl MyHandler {
-    fn new() -> Self {
-        Self {}
-    }
-}
-
-impl SimpleIppServiceHandler for MyHandler {
-    fn handle_document(
-        &self,
-        document: SimpleIppDocument,
-    ) -> impl futures::Future<Output = anyhow::Result<()>> + Send {
-        async move {
-            println!("Received document: {:#?}", document);
-            let mut file = File::create("D:\\1.pdf").await?;
-            io::copy(&mut document.payload.compat(), &mut file).await?;
-            Ok(())
-        }
-    }
-}
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 631);
@@ -65,7 +42,11 @@ async fn main() -> anyhow::Result<()> {
         .orientation_supported(vec![PageOrientation::Portrait, PageOrientation::Landscape])
         .build()
         .unwrap();
-    let ipp_service = Arc::new(SimpleIppService::new(info, MyHandler::new()));
+    let handler = WriteSink::new(|document: &SimpleIppDocument| {
+        println!("Received document: {:#?}", document);
+        async { Ok(File::create("D:\\1.pdf").await?.compat_write()) }
+    });
+    let ipp_service = Arc::new(SimpleIppService::new(info, handler));
     const CERTIFICATE: &[u8] = include_bytes!("certificate/certificate.pem");
     const KEY: &[u8] = include_bytes!("certificate/key.pem");
     let tls_config =
@@ -1,4 +1,4 @@
-use crate::utils::ReaderStream;
+use crate::utils::{ReaderStream, DEFAULT_BUDGET};
 use bytes::Bytes;
 use futures::stream::Stream;
 use http_body::Body as HttpBody;
@@ -28,6 +28,17 @@ impl Body {
             inner: BodyInner::Empty,
         }
     }
+
+    /// Stream an IPP response, yielding to the runtime after `budget` consecutive
+    /// ready reads of its payload, or never if `None`.
+    pub(crate) fn from_ipp_with_budget(t: IppRequestResponse, budget: Option<usize>) -> Body {
+        Body {
+            inner: BodyInner::IppRequestResponse {
+                header: Some(t.to_bytes()),
+                payload: ReaderStream::with_budget(t.into_payload(), budget),
+            },
+        }
+    }
 }
 
 impl Stream for Body {
@@ -89,11 +100,6 @@ impl From<Bytes> for Body {
 
 impl From<IppRequestResponse> for Body {
     fn from(t: IppRequestResponse) -> Body {
-        Body {
-            inner: BodyInner::IppRequestResponse {
-                header: Some(t.to_bytes()),
-                payload: ReaderStream::new(t.into_payload()),
-            },
-        }
+        Body::from_ipp_with_budget(t, Some(DEFAULT_BUDGET))
     }
 }
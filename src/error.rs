@@ -1,4 +1,5 @@
 use ipp::model::StatusCode;
+use ipp::value::IppValue;
 use thiserror::Error;
 
 #[derive(Error, Debug, Clone)]
@@ -7,3 +8,12 @@ pub struct IppError {
     pub code: StatusCode,
     pub msg: String,
 }
+
+/// An `IppError` together with the attributes that caused it,
+/// which are returned to the client in the unsupported-attributes group.
+#[derive(Error, Debug, Clone)]
+#[error("{error}")]
+pub struct IppRejection {
+    pub error: IppError,
+    pub unsupported_attributes: Vec<(String, IppValue)>,
+}
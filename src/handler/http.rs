@@ -1,11 +1,99 @@
 use crate::body::Body;
 use crate::body_reader::BodyReader;
+use crate::error::IppError;
 use crate::service::IppService;
+use crate::utils::{LimitedReader, ReadLimit, DEFAULT_BUDGET};
 use anyhow;
-use bytes::Buf;
-use http::{Method, Request, Response, StatusCode};
+use bytes::{Buf, Bytes};
+use futures::io::{AsyncReadExt, Cursor};
+use futures::FutureExt;
+use http::{header, HeaderMap, HeaderValue, Method, Request, Response, StatusCode};
 use http_body::Body as HttpBody;
+use ipp::model::IppVersion;
 use ipp::parser::AsyncIppParser;
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+
+/// Options for serving IPP over HTTP
+#[derive(Debug, Clone, Builder)]
+pub struct IppHttpConfig {
+    /// Maximum size in bytes of the IPP header and attributes, not counting the document.  
+    /// This is a DoS mitigation: requests with more attributes are rejected with
+    /// `client-error-request-entity-too-large` before they exhaust memory in the parser.
+    #[builder(default = r#"1024 * 1024"#)]
+    max_attributes_size: u64,
+    /// Headers added to every response, such as `Strict-Transport-Security`.  
+    /// `Content-Type` is never overridden.
+    #[builder(default = r#"HeaderMap::new()"#)]
+    extra_response_headers: HeaderMap,
+    /// HTTP methods that carry IPP requests. Only POST is correct per RFC 8010,
+    /// but some embedded clients and proxies use others.
+    #[builder(default = r#"vec![Method::POST]"#)]
+    methods: Vec<Method>,
+    /// Path of the IPP endpoint, e.g. `/ipp/print`. IPP is served at any path by default.
+    #[builder(default = r#"None"#)]
+    path: Option<String>,
+    /// Body of the response to requests using a method other than `methods`
+    #[builder(default = r#"Bytes::from_static(b"405 Method Not Allowed")"#)]
+    method_not_allowed_body: Bytes,
+    /// Body of the response to requests for a path other than `path`
+    #[builder(default = r#"Bytes::from_static(b"404 Not Found")"#)]
+    not_found_body: Bytes,
+    /// Body of the response to requests whose `Content-Type` is not `application/ipp`
+    #[builder(default = r#"Bytes::from_static(b"415 Unsupported Media Type")"#)]
+    unsupported_media_type_body: Bytes,
+    /// `Content-Type` of the bodies above, e.g. `text/html` for branded error pages.  
+    /// No `Content-Type` is sent by default.
+    #[builder(default = r#"None"#)]
+    error_body_content_type: Option<HeaderValue>,
+    /// Number of consecutive chunks of a response document that are read without the
+    /// document ever being pending, after which the response yields to the runtime once,
+    /// so that fast documents do not starve other connections. `None` never yields.
+    #[builder(default = r#"Some(DEFAULT_BUDGET)"#)]
+    read_budget: Option<usize>,
+}
+
+impl Default for IppHttpConfig {
+    fn default() -> Self {
+        IppHttpConfigBuilder::default().build().unwrap()
+    }
+}
+
+/// Check the media type case-insensitively, ignoring any parameters such as `charset`.
+fn is_ipp_content_type(value: Option<&HeaderValue>) -> bool {
+    value
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.split(';').next())
+        .is_some_and(|x| x.trim().eq_ignore_ascii_case("application/ipp"))
+}
+
+fn error_response(status: StatusCode, body: &Bytes, config: &IppHttpConfig) -> Response<Body> {
+    let mut response = Response::builder().status(status);
+    if let Some(ref content_type) = config.error_body_content_type {
+        response = response.header(header::CONTENT_TYPE, content_type.clone());
+    }
+    response.body(Body::from(body.clone())).unwrap()
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+fn add_extra_headers(mut response: Response<Body>, config: &IppHttpConfig) -> Response<Body> {
+    let headers = response.headers_mut();
+    for (name, value) in config.extra_response_headers.iter() {
+        if name != header::CONTENT_TYPE {
+            headers.append(name, value.clone());
+        }
+    }
+    response
+}
 
 pub async fn handle_ipp_via_http<ReqBody, ReqData, ReqError>(
     req: Request<ReqBody>,
@@ -16,27 +104,244 @@ where
     ReqError: std::error::Error + Send + Sync + 'static,
     ReqBody: HttpBody<Data = ReqData, Error = ReqError> + Send + Sync + Unpin + 'static,
 {
-    if req.method() != Method::POST {
-        return Ok(Response::builder()
-            .status(StatusCode::METHOD_NOT_ALLOWED)
-            .header("Allow", "POST")
-            .body(Body::from("405 Method Not Allowed"))
-            .unwrap());
-    }
-    if req.headers().get("Content-Type") != Some(&"application/ipp".parse().unwrap()) {
-        return Ok(Response::builder()
-            .status(StatusCode::UNSUPPORTED_MEDIA_TYPE)
-            .body(Body::from("415 Unsupported Media Type"))
-            .unwrap());
+    handle_ipp_via_http_with_config(req, handler, &IppHttpConfig::default()).await
+}
+
+pub async fn handle_ipp_via_http_with_config<ReqBody, ReqData, ReqError>(
+    req: Request<ReqBody>,
+    handler: &impl IppService,
+    config: &IppHttpConfig,
+) -> Result<Response<Body>, anyhow::Error>
+where
+    ReqData: Buf + Send + Sync + Unpin + 'static,
+    ReqError: std::error::Error + Send + Sync + 'static,
+    ReqBody: HttpBody<Data = ReqData, Error = ReqError> + Send + Sync + Unpin + 'static,
+{
+    let response = handle_ipp_via_http_inner(req, handler, config).await?;
+    Ok(add_extra_headers(response, config))
+}
+
+async fn handle_ipp_via_http_inner<ReqBody, ReqData, ReqError>(
+    req: Request<ReqBody>,
+    handler: &impl IppService,
+    config: &IppHttpConfig,
+) -> Result<Response<Body>, anyhow::Error>
+where
+    ReqData: Buf + Send + Sync + Unpin + 'static,
+    ReqError: std::error::Error + Send + Sync + 'static,
+    ReqBody: HttpBody<Data = ReqData, Error = ReqError> + Send + Sync + Unpin + 'static,
+{
+    if config
+        .path
+        .as_ref()
+        .is_some_and(|path| req.uri().path() != path)
+    {
+        return Ok(error_response(
+            StatusCode::NOT_FOUND,
+            &config.not_found_body,
+            config,
+        ));
+    }
+    #[cfg(feature = "json-status")]
+    if req.method() == Method::GET && super::json::accepts_json(req.headers()) {
+        let (head, _) = req.into_parts();
+        return Ok(super::json::printer_status(head, handler).await);
+    }
+    if !config.methods.contains(req.method()) {
+        let mut response = error_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            &config.method_not_allowed_body,
+            config,
+        );
+        let allow = config
+            .methods
+            .iter()
+            .map(|x| x.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if let Ok(allow) = HeaderValue::from_str(&allow) {
+            response.headers_mut().insert(header::ALLOW, allow);
+        }
+        return Ok(response);
+    }
+    if !is_ipp_content_type(req.headers().get("Content-Type")) {
+        return Ok(error_response(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            &config.unsupported_media_type_body,
+            config,
+        ));
     }
     let (head, body) = req.into_parts();
-    let reader = BodyReader::new(body);
-    let ipp_request = AsyncIppParser::new(reader).parse().await?;
-    let response = handler.handle_request(head, ipp_request).await;
-    let body = Body::from(response);
+    let limit = ReadLimit::new(config.max_attributes_size);
+    let mut reader = LimitedReader::new(BodyReader::new(body), limit.clone());
+    // Read the fixed-size IPP header first, so that an error response can be built
+    // with the right version and request id if the attributes turn out to be too large
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header).await?;
+    let version = IppVersion(u16::from_be_bytes([header[0], header[1]]));
+    let req_id = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let response = match AsyncIppParser::new(Cursor::new(header).chain(reader))
+        .parse()
+        .await
+    {
+        Ok(ipp_request) => {
+            limit.lift();
+            // Turn a panic of the service into an error response rather than a connection reset
+            match AssertUnwindSafe(handler.handle_request(head, ipp_request))
+                .catch_unwind()
+                .await
+            {
+                Ok(response) => response,
+                Err(panic) => {
+                    log::error!("Panic while handling request: {}", panic_message(&*panic));
+                    handler.build_error_response(
+                        version,
+                        req_id,
+                        IppError {
+                            code: ipp::model::StatusCode::ServerErrorInternalError,
+                            msg: "Internal error".to_string(),
+                        }
+                        .into(),
+                    )
+                }
+            }
+        }
+        Err(_) if limit.exceeded() => handler.build_error_response(
+            version,
+            req_id,
+            IppError {
+                code: ipp::model::StatusCode::ClientErrorRequestEntityTooLong,
+                msg: "IPP attributes are too large".to_string(),
+            }
+            .into(),
+        ),
+        Err(error) => return Err(error.into()),
+    };
+    let body = Body::from_ipp_with_budget(response, config.read_budget);
     Ok(Response::builder()
         .status(200)
-        .header("Content-Type", "application/ipp")
+        .header(header::CONTENT_TYPE, "application/ipp")
         .body(body)
         .unwrap())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::simple::{PrinterInfoBuilder, SimpleIppService};
+    use crate::service::testing::{add_attribute, request, RecordingHandler};
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use http_body_util::Full;
+    use ipp::model::{DelimiterTag, Operation};
+    use ipp::value::IppValue;
+
+    /// An encoded Get-Printer-Attributes request padded with a `job-name` of `padding` bytes
+    fn padded_request(padding: usize) -> Bytes {
+        let mut req = request(Operation::GetPrinterAttributes);
+        req.header_mut().request_id = 7;
+        add_attribute(
+            &mut req,
+            DelimiterTag::OperationAttributes,
+            "job-name",
+            IppValue::NameWithoutLanguage("x".repeat(padding)),
+        );
+        req.to_bytes()
+    }
+
+    /// Post `body` with `config` and return the status code and request id of the IPP response
+    fn post(
+        service: &impl IppService,
+        body: Bytes,
+        config: &IppHttpConfig,
+    ) -> (ipp::model::StatusCode, u32) {
+        let req = Request::post("/")
+            .header(header::CONTENT_TYPE, "application/ipp")
+            .body(Full::new(body))
+            .unwrap();
+        let resp = block_on(handle_ipp_via_http_with_config(req, service, config)).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let header = block_on(resp.into_body().next()).unwrap().unwrap();
+        let parsed = ipp::parser::IppParser::new(std::io::Cursor::new(header))
+            .parse()
+            .unwrap();
+        (parsed.header().status_code(), parsed.header().request_id)
+    }
+
+    fn simple_service() -> SimpleIppService<RecordingHandler> {
+        SimpleIppService::new(
+            PrinterInfoBuilder::default().build().unwrap(),
+            RecordingHandler::default(),
+        )
+    }
+
+    #[test]
+    fn attributes_over_the_limit_are_too_long() {
+        let body = padded_request(4096);
+        let config = IppHttpConfigBuilder::default()
+            .max_attributes_size(body.len() as u64 - 1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            post(&simple_service(), body, &config),
+            (ipp::model::StatusCode::ClientErrorRequestEntityTooLong, 7)
+        );
+    }
+
+    #[test]
+    fn attributes_within_the_limit_are_accepted() {
+        let body = padded_request(4096);
+        let config = IppHttpConfigBuilder::default()
+            .max_attributes_size(body.len() as u64)
+            .build()
+            .unwrap();
+        assert_eq!(
+            post(&simple_service(), body, &config),
+            (ipp::model::StatusCode::SuccessfulOk, 7)
+        );
+    }
+
+    fn accepts(content_type: &'static str) -> bool {
+        is_ipp_content_type(Some(&HeaderValue::from_static(content_type)))
+    }
+
+    /// Panics on Get-Printer-Attributes
+    struct Panicking;
+
+    impl IppService for Panicking {
+        fn version(&self) -> IppVersion {
+            IppVersion::v2_0()
+        }
+        async fn get_printer_attributes(
+            &self,
+            _head: http::request::Parts,
+            _req: ipp::request::IppRequestResponse,
+        ) -> crate::result::IppResult {
+            panic!("broken service")
+        }
+    }
+
+    #[test]
+    fn panicking_service_returns_internal_error() {
+        assert_eq!(
+            post(&Panicking, padded_request(0), &IppHttpConfig::default()),
+            (ipp::model::StatusCode::ServerErrorInternalError, 7)
+        );
+    }
+
+    #[test]
+    fn ipp_content_type_ignores_case_and_parameters() {
+        assert!(accepts("application/ipp"));
+        assert!(accepts("Application/IPP"));
+        assert!(accepts("application/ipp; charset=utf-8"));
+        assert!(accepts(" APPLICATION/ipp ;charset=utf-8"));
+    }
+
+    #[test]
+    fn other_content_types_are_rejected() {
+        assert!(!accepts("application/ipps"));
+        assert!(!accepts("application/octet-stream"));
+        assert!(!accepts("text/plain; x=application/ipp"));
+        assert!(!is_ipp_content_type(None));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::body::Body;
+use crate::service::IppService;
+use http::request::Parts as ReqParts;
+use http::{header, HeaderMap, Response, StatusCode};
+use ipp::attribute::IppAttribute;
+use ipp::model::{DelimiterTag, IppVersion, Operation};
+use ipp::request::IppRequestResponse;
+use ipp::value::IppValue;
+use serde_json::{Map, Value};
+
+/// Attributes summarized for `GET` requests asking for JSON
+const STATUS_ATTRIBUTES: [&str; 13] = [
+    IppAttribute::PRINTER_NAME,
+    IppAttribute::PRINTER_INFO,
+    IppAttribute::PRINTER_LOCATION,
+    IppAttribute::PRINTER_MAKE_AND_MODEL,
+    IppAttribute::PRINTER_UUID,
+    IppAttribute::PRINTER_URI_SUPPORTED,
+    IppAttribute::PRINTER_STATE,
+    IppAttribute::PRINTER_STATE_REASONS,
+    IppAttribute::PRINTER_IS_ACCEPTING_JOBS,
+    IppAttribute::COLOR_SUPPORTED,
+    IppAttribute::DOCUMENT_FORMAT_SUPPORTED,
+    IppAttribute::MEDIA_SUPPORTED,
+    IppAttribute::SIDES_SUPPORTED,
+];
+
+pub(crate) fn accepts_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|x| x.to_str().ok())
+        .flat_map(|x| x.split(','))
+        .filter_map(|x| x.split(';').next())
+        .any(|x| x.trim().eq_ignore_ascii_case("application/json"))
+}
+
+fn to_json(value: &IppValue) -> Value {
+    match value {
+        IppValue::Integer(x) | IppValue::Enum(x) => Value::from(*x),
+        IppValue::Boolean(x) => Value::from(*x),
+        IppValue::Array(x) => x.iter().map(to_json).collect(),
+        IppValue::Collection(x) => Value::Object(
+            x.iter()
+                .map(|(key, value)| (key.clone(), to_json(value)))
+                .collect(),
+        ),
+        IppValue::NoValue => Value::Null,
+        x => Value::from(x.to_string()),
+    }
+}
+
+/// Answer a `GET` with the key printer attributes as a JSON object,
+/// obtained through a regular Get-Printer-Attributes request.
+pub(crate) async fn printer_status(head: ReqParts, handler: &impl IppService) -> Response<Body> {
+    let mut req =
+        IppRequestResponse::new(IppVersion::v1_1(), Operation::GetPrinterAttributes, None);
+    req.attributes_mut().add(
+        DelimiterTag::OperationAttributes,
+        IppAttribute::new(
+            IppAttribute::REQUESTED_ATTRIBUTES,
+            IppValue::Array(
+                STATUS_ATTRIBUTES
+                    .into_iter()
+                    .map(|x| IppValue::Keyword(x.to_string()))
+                    .collect(),
+            ),
+        ),
+    );
+    let resp = handler.handle_request(head, req).await;
+    let attributes = resp
+        .attributes()
+        .groups_of(DelimiterTag::PrinterAttributes)
+        .flat_map(|group| group.attributes().values())
+        .map(|attr| (attr.name().to_owned(), to_json(attr.value())))
+        .collect::<Map<_, _>>();
+    let status = if resp.header().operation_or_status < 0x0100 {
+        StatusCode::OK
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(Value::Object(attributes).to_string()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::simple::{PrinterInfoBuilder, SimpleIppService};
+    use crate::service::testing::{head, RecordingHandler};
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use http::HeaderValue;
+
+    fn accepts(accept: &[&'static str]) -> bool {
+        let mut headers = HeaderMap::new();
+        for x in accept {
+            headers.append(header::ACCEPT, HeaderValue::from_static(x));
+        }
+        accepts_json(&headers)
+    }
+
+    #[test]
+    fn json_is_accepted_among_other_media_types() {
+        assert!(accepts(&["application/json"]));
+        assert!(accepts(&["Application/JSON; charset=utf-8"]));
+        assert!(accepts(&["text/html, application/json;q=0.9"]));
+        assert!(accepts(&["text/html", "application/json"]));
+        assert!(!accepts(&[]));
+        assert!(!accepts(&["text/html, */*"]));
+        assert!(!accepts(&["application/jsonx"]));
+    }
+
+    #[test]
+    fn printer_status_summarizes_printer_attributes() {
+        let service = SimpleIppService::new(
+            PrinterInfoBuilder::default().build().unwrap(),
+            RecordingHandler::default(),
+        );
+        let resp = block_on(printer_status(head(), &service));
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        let body = block_on(resp.into_body().next()).unwrap().unwrap();
+        let status = serde_json::from_slice::<Value>(&body).unwrap();
+        let status = status.as_object().unwrap();
+        assert!(status
+            .keys()
+            .all(|x| STATUS_ATTRIBUTES.contains(&x.as_str())));
+        assert_eq!(status[IppAttribute::PRINTER_NAME], "IppServer");
+        assert!(status[IppAttribute::PRINTER_STATE].is_i64());
+        assert!(status[IppAttribute::PRINTER_IS_ACCEPTING_JOBS].is_boolean());
+        assert!(status[IppAttribute::DOCUMENT_FORMAT_SUPPORTED].is_array());
+    }
+}
@@ -1,2 +1,6 @@
 mod http;
-pub use http::handle_ipp_via_http;
+#[cfg(feature = "json-status")]
+mod json;
+pub use http::{
+    handle_ipp_via_http, handle_ipp_via_http_with_config, IppHttpConfig, IppHttpConfigBuilder,
+};
@@ -1,4 +1,59 @@
 use ipp::{model::JobState, value::IppValue};
+use num_traits::FromPrimitive;
+use std::borrow::Cow;
+
+pub use ipp::model::Operation;
+
+/// Operation codes missing from `ipp::model::Operation`
+pub mod operation_code {
+    /// Set-Printer-Attributes, RFC 3380
+    pub const SET_PRINTER_ATTRIBUTES: u16 = 0x0013;
+}
+
+/// The name of the operation `code` (e.g. `Print-Job`) for logging,
+/// or `unknown(0xNNNN)` for codes this crate does not know.
+pub fn operation_name(code: u16) -> Cow<'static, str> {
+    if code == operation_code::SET_PRINTER_ATTRIBUTES {
+        return Cow::Borrowed("Set-Printer-Attributes");
+    }
+    let name = match Operation::from_u16(code) {
+        Some(Operation::PrintJob) => "Print-Job",
+        Some(Operation::PrintUri) => "Print-URI",
+        Some(Operation::ValidateJob) => "Validate-Job",
+        Some(Operation::CreateJob) => "Create-Job",
+        Some(Operation::SendDocument) => "Send-Document",
+        Some(Operation::SendUri) => "Send-URI",
+        Some(Operation::CancelJob) => "Cancel-Job",
+        Some(Operation::GetJobAttributes) => "Get-Job-Attributes",
+        Some(Operation::GetJobs) => "Get-Jobs",
+        Some(Operation::GetPrinterAttributes) => "Get-Printer-Attributes",
+        Some(Operation::HoldJob) => "Hold-Job",
+        Some(Operation::ReleaseJob) => "Release-Job",
+        Some(Operation::RestartJob) => "Restart-Job",
+        Some(Operation::PausePrinter) => "Pause-Printer",
+        Some(Operation::ResumePrinter) => "Resume-Printer",
+        Some(Operation::PurgeJobs) => "Purge-Jobs",
+        Some(Operation::CupsGetDefault) => "CUPS-Get-Default",
+        Some(Operation::CupsGetPrinters) => "CUPS-Get-Printers",
+        Some(Operation::CupsAddModifyPrinter) => "CUPS-Add-Modify-Printer",
+        Some(Operation::CupsDeletePrinter) => "CUPS-Delete-Printer",
+        Some(Operation::CupsGetClasses) => "CUPS-Get-Classes",
+        Some(Operation::CupsAddModifyClass) => "CUPS-Add-Modify-Class",
+        Some(Operation::CupsDeleteClass) => "CUPS-Delete-Class",
+        Some(Operation::CupsAcceptJobs) => "CUPS-Accept-Jobs",
+        Some(Operation::CupsRejectJobs) => "CUPS-Reject-Jobs",
+        Some(Operation::CupsSetDefault) => "CUPS-Set-Default",
+        Some(Operation::CupsGetDevices) => "CUPS-Get-Devices",
+        Some(Operation::CupsGetPPDs) => "CUPS-Get-PPDs",
+        Some(Operation::CupsMoveJob) => "CUPS-Move-Job",
+        Some(Operation::CupsAuthenticateJob) => "CUPS-Authenticate-Job",
+        Some(Operation::CupsGetPPD) => "CUPS-Get-PPD",
+        Some(Operation::CupsGetDocument) => "CUPS-Get-Document",
+        Some(Operation::CupsCreateLocalPrinter) => "CUPS-Create-Local-Printer",
+        None => return Cow::Owned(format!("unknown({:#06x})", code)),
+    };
+    Cow::Borrowed(name)
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PageOrientation {
@@ -67,6 +122,32 @@ impl Resolution {
             units: 4,
         }
     }
+
+    /// Convert to dots per inch as `(cross_feed, feed)`, or `None` for unknown units.
+    pub fn to_dpi(&self) -> Option<(f64, f64)> {
+        let factor = match self.units {
+            3 => 1.0,
+            4 => 2.54,
+            _ => return None,
+        };
+        Some((self.cross_feed as f64 * factor, self.feed as f64 * factor))
+    }
+
+    /// Compare two resolutions, which may be expressed in different units.  
+    /// Both are converted to dots per inch, and allowed to differ by 0.5%
+    /// to absorb the rounding of integer dpcm values.
+    pub fn approx_eq(&self, other: &Resolution) -> bool {
+        if self == other {
+            return true;
+        }
+        fn close(a: f64, b: f64) -> bool {
+            (a - b).abs() <= a.max(b) * 0.005
+        }
+        match (self.to_dpi(), other.to_dpi()) {
+            (Some((x1, y1)), Some((x2, y2))) => close(x1, x2) && close(y1, y2),
+            _ => false,
+        }
+    }
 }
 
 impl TryFrom<IppValue> for Resolution {
@@ -100,6 +181,61 @@ impl From<Resolution> for IppValue {
     }
 }
 
+/// Dimensions of a media, in hundredths of millimeters as in the `media-size` member of `media-col`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MediaSize {
+    pub x_dimension: i32,
+    pub y_dimension: i32,
+}
+
+impl MediaSize {
+    /// Parse the dimensions of a PWG 5101.1 self-describing media name,
+    /// such as `iso_a4_210x297mm` or `na_letter_8.5x11in`.
+    pub fn from_pwg_name(name: &str) -> Option<Self> {
+        let dimensions = name.rsplit('_').next()?;
+        let (dimensions, factor) = if let Some(x) = dimensions.strip_suffix("mm") {
+            (x, 100.0)
+        } else if let Some(x) = dimensions.strip_suffix("in") {
+            (x, 2540.0)
+        } else {
+            return None;
+        };
+        let (x, y) = dimensions.split_once('x')?;
+        let x: f64 = x.parse().ok()?;
+        let y: f64 = y.parse().ok()?;
+        Some(Self {
+            x_dimension: (x * factor).round() as i32,
+            y_dimension: (y * factor).round() as i32,
+        })
+    }
+
+    /// The larger of the differences between the two widths and the two heights
+    pub fn distance(&self, other: &MediaSize) -> i32 {
+        (self.x_dimension - other.x_dimension)
+            .abs()
+            .max((self.y_dimension - other.y_dimension).abs())
+    }
+}
+
+impl TryFrom<IppValue> for MediaSize {
+    type Error = IppValue;
+
+    fn try_from(value: IppValue) -> Result<Self, IppValue> {
+        let IppValue::Collection(members) = &value else {
+            return Err(value);
+        };
+        match (members.get("x-dimension"), members.get("y-dimension")) {
+            (Some(IppValue::Integer(x_dimension)), Some(IppValue::Integer(y_dimension))) => {
+                Ok(Self {
+                    x_dimension: *x_dimension,
+                    y_dimension: *y_dimension,
+                })
+            }
+            _ => Err(value),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum WhichJob {
     NotCompleted,
@@ -114,6 +250,35 @@ pub enum WhichJob {
 }
 
 impl WhichJob {
+    pub const ALL: [WhichJob; 9] = [
+        WhichJob::Completed,
+        WhichJob::NotCompleted,
+        WhichJob::Aborted,
+        WhichJob::All,
+        WhichJob::Canceled,
+        WhichJob::Pending,
+        WhichJob::PendingHeld,
+        WhichJob::Processing,
+        WhichJob::ProcessingStopped,
+    ];
+
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            WhichJob::NotCompleted => "not-completed",
+            WhichJob::Completed => "completed",
+            WhichJob::Aborted => "aborted",
+            WhichJob::All => "all",
+            WhichJob::Canceled => "canceled",
+            WhichJob::Pending => "pending",
+            WhichJob::PendingHeld => "pending-held",
+            WhichJob::Processing => "processing",
+            WhichJob::ProcessingStopped => "processing-stopped",
+        }
+    }
+
+    /// Check whether a job in `state` is selected, following RFC 8011:
+    /// `completed` covers completed, canceled and aborted jobs,
+    /// `not-completed` covers pending, held, processing and stopped jobs.
     pub fn match_state(&self, state: JobState) -> bool {
         match self {
             WhichJob::NotCompleted => matches!(
@@ -136,4 +301,425 @@ impl WhichJob {
             WhichJob::Aborted => state == JobState::Aborted,
         }
     }
+
+    /// Whether the selected jobs are listed most recent first, as RFC 8011 asks for
+    /// finished jobs. Other jobs are listed in the order they will be processed.
+    pub fn newest_first(&self) -> bool {
+        matches!(
+            self,
+            WhichJob::Completed | WhichJob::Canceled | WhichJob::Aborted
+        )
+    }
+}
+
+impl<'a> TryFrom<&'a str> for WhichJob {
+    type Error = &'a str;
+
+    fn try_from(value: &'a str) -> Result<Self, &'a str> {
+        WhichJob::ALL
+            .into_iter()
+            .find(|x| x.keyword() == value)
+            .ok_or(value)
+    }
+}
+
+/// How the documents and copies of a job are arranged, see RFC 8011 section 5.2.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MultipleDocumentHandling {
+    SingleDocument,
+    SeparateDocumentsUncollatedCopies,
+    SeparateDocumentsCollatedCopies,
+    SingleDocumentNewSheet,
+}
+
+impl MultipleDocumentHandling {
+    pub const ALL: [MultipleDocumentHandling; 4] = [
+        MultipleDocumentHandling::SingleDocument,
+        MultipleDocumentHandling::SeparateDocumentsUncollatedCopies,
+        MultipleDocumentHandling::SeparateDocumentsCollatedCopies,
+        MultipleDocumentHandling::SingleDocumentNewSheet,
+    ];
+
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            MultipleDocumentHandling::SingleDocument => "single-document",
+            MultipleDocumentHandling::SeparateDocumentsUncollatedCopies => {
+                "separate-documents-uncollated-copies"
+            }
+            MultipleDocumentHandling::SeparateDocumentsCollatedCopies => {
+                "separate-documents-collated-copies"
+            }
+            MultipleDocumentHandling::SingleDocumentNewSheet => "single-document-new-sheet",
+        }
+    }
+
+    /// Whether copies are collated, i.e. printed as complete sets one after another
+    pub fn collated(&self) -> bool {
+        !matches!(
+            self,
+            MultipleDocumentHandling::SeparateDocumentsUncollatedCopies
+        )
+    }
+}
+
+impl<'a> TryFrom<&'a str> for MultipleDocumentHandling {
+    type Error = &'a str;
+
+    fn try_from(value: &'a str) -> Result<Self, &'a str> {
+        MultipleDocumentHandling::ALL
+            .into_iter()
+            .find(|x| x.keyword() == value)
+            .ok_or(value)
+    }
+}
+
+impl From<MultipleDocumentHandling> for IppValue {
+    fn from(value: MultipleDocumentHandling) -> Self {
+        IppValue::Keyword(value.keyword().to_string())
+    }
+}
+
+/// The kind of content a job is optimized for (`print-content-optimize`), a rendering hint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrintContentOptimize {
+    Auto,
+    Graphic,
+    Photo,
+    Text,
+    TextAndGraphic,
+}
+
+impl PrintContentOptimize {
+    pub const ALL: [PrintContentOptimize; 5] = [
+        PrintContentOptimize::Auto,
+        PrintContentOptimize::Graphic,
+        PrintContentOptimize::Photo,
+        PrintContentOptimize::Text,
+        PrintContentOptimize::TextAndGraphic,
+    ];
+
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            PrintContentOptimize::Auto => "auto",
+            PrintContentOptimize::Graphic => "graphic",
+            PrintContentOptimize::Photo => "photo",
+            PrintContentOptimize::Text => "text",
+            PrintContentOptimize::TextAndGraphic => "text-and-graphic",
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for PrintContentOptimize {
+    type Error = &'a str;
+
+    fn try_from(value: &'a str) -> Result<Self, &'a str> {
+        PrintContentOptimize::ALL
+            .into_iter()
+            .find(|x| x.keyword() == value)
+            .ok_or(value)
+    }
+}
+
+impl From<PrintContentOptimize> for IppValue {
+    fn from(value: PrintContentOptimize) -> Self {
+        IppValue::Keyword(value.keyword().to_string())
+    }
+}
+
+/// Values of `compression` that the service knows how to decompress
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    None,
+    Gzip,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 2] = [Compression::None, Compression::Gzip];
+
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Compression {
+    type Error = &'a str;
+
+    fn try_from(value: &'a str) -> Result<Self, &'a str> {
+        Compression::ALL
+            .into_iter()
+            .find(|x| x.keyword() == value)
+            .ok_or(value)
+    }
+}
+
+/// Severity suffix of a `printer-state-reasons` keyword, see RFC 8011 section 5.4.12
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReasonSeverity {
+    /// Informational only
+    Report,
+    /// The printer keeps working, possibly degraded
+    Warning,
+    /// The printer is stopped
+    Error,
+}
+
+impl ReasonSeverity {
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            ReasonSeverity::Report => "-report",
+            ReasonSeverity::Warning => "-warning",
+            ReasonSeverity::Error => "-error",
+        }
+    }
+}
+
+/// A `printer-state-reasons` value, such as `connecting-to-device-report`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrinterStateReason {
+    pub keyword: String,
+    pub severity: ReasonSeverity,
+}
+
+impl PrinterStateReason {
+    pub fn new(keyword: &str, severity: ReasonSeverity) -> Self {
+        PrinterStateReason {
+            keyword: keyword.to_string(),
+            severity,
+        }
+    }
+
+    /// The keyword with its severity suffix appended
+    pub fn to_keyword(&self) -> String {
+        format!("{}{}", self.keyword, self.severity.suffix())
+    }
+}
+
+/// A supply of the printer, such as a toner cartridge or a waste toner box,
+/// reported in `printer-supply` and `printer-supply-description` (PWG 5100.13)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrinterSupply {
+    /// `type`, e.g. `toner`, `ink` or `wasteToner`
+    pub supply_type: String,
+    /// `class`, `supplyThatIsConsumed` or `receptacleThatIsFilled`
+    pub class: String,
+    /// `colorantname`, e.g. `black` or `cyan`
+    pub colorant_name: Option<String>,
+    /// `level`, in `unit`s; `-2` means unknown and `-3` means some remaining
+    pub level: i32,
+    /// `maxcapacity`, in `unit`s
+    pub max_capacity: i32,
+    /// `unit`, e.g. `percent`
+    pub unit: String,
+    /// Human-readable name, reported in `printer-supply-description`
+    pub description: String,
+}
+
+impl PrinterSupply {
+    /// A consumed supply whose level is given in percent
+    pub fn new(supply_type: &str, description: &str, level: i32) -> Self {
+        PrinterSupply {
+            supply_type: supply_type.to_string(),
+            class: "supplyThatIsConsumed".to_string(),
+            colorant_name: None,
+            level,
+            max_capacity: 100,
+            unit: "percent".to_string(),
+            description: description.to_string(),
+        }
+    }
+
+    /// The `printer-supply` value of the supply at 1-based `index`
+    pub fn to_octet_string(&self, index: usize) -> String {
+        let mut value = format!(
+            "index={};class={};type={};unit={};maxcapacity={};level={};",
+            index, self.class, self.supply_type, self.unit, self.max_capacity, self.level
+        );
+        if let Some(colorant_name) = &self.colorant_name {
+            value.push_str(&format!("colorantname={};", colorant_name));
+        }
+        value
+    }
+}
+
+/// A `finishings` enum value, such as `staple` or `punch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Finishing(pub i32);
+
+impl Finishing {
+    pub const NONE: Finishing = Finishing(3);
+    pub const STAPLE: Finishing = Finishing(4);
+    pub const PUNCH: Finishing = Finishing(5);
+    pub const COVER: Finishing = Finishing(6);
+    pub const BIND: Finishing = Finishing(7);
+    pub const SADDLE_STITCH: Finishing = Finishing(8);
+    pub const EDGE_STITCH: Finishing = Finishing(9);
+    pub const FOLD: Finishing = Finishing(10);
+}
+
+impl TryFrom<IppValue> for Finishing {
+    type Error = IppValue;
+
+    fn try_from(value: IppValue) -> Result<Self, IppValue> {
+        match value {
+            IppValue::Enum(v) => Ok(Self(v)),
+            _ => Err(value),
+        }
+    }
+}
+
+impl From<Finishing> for IppValue {
+    fn from(value: Finishing) -> Self {
+        IppValue::Enum(value.0)
+    }
+}
+
+/// The keyword of `state`, e.g. `pending-held`.
+pub fn job_state_keyword(state: JobState) -> &'static str {
+    match state {
+        JobState::Pending => "pending",
+        JobState::PendingHeld => "pending-held",
+        JobState::Processing => "processing",
+        JobState::ProcessingStopped => "processing-stopped",
+        JobState::Canceled => "canceled",
+        JobState::Aborted => "aborted",
+        JobState::Completed => "completed",
+    }
+}
+
+/// Check whether a job may move from `from` to `to`, following the state diagram of RFC 8011.  
+/// Pending-held and processing may move into each other directly, as a shorthand for
+/// passing through pending. Terminal states may only go back to pending, as by Restart-Job.
+pub fn job_state_transition_allowed(from: JobState, to: JobState) -> bool {
+    use JobState::*;
+    matches!(
+        (from, to),
+        (Pending, PendingHeld | Processing | Canceled | Aborted)
+            | (PendingHeld, Pending | Processing | Canceled | Aborted)
+            | (
+                Processing,
+                PendingHeld | ProcessingStopped | Canceled | Aborted | Completed
+            )
+            | (
+                ProcessingStopped,
+                Processing | Canceled | Aborted | Completed
+            )
+            | (Canceled | Aborted | Completed, Pending)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JOB_STATES: [JobState; 7] = [
+        JobState::Pending,
+        JobState::PendingHeld,
+        JobState::Processing,
+        JobState::ProcessingStopped,
+        JobState::Canceled,
+        JobState::Aborted,
+        JobState::Completed,
+    ];
+
+    fn selected(which: WhichJob) -> Vec<JobState> {
+        JOB_STATES
+            .into_iter()
+            .filter(|x| which.match_state(*x))
+            .collect()
+    }
+
+    #[test]
+    fn completed_and_not_completed_partition_job_states() {
+        assert_eq!(
+            selected(WhichJob::Completed),
+            vec![JobState::Canceled, JobState::Aborted, JobState::Completed]
+        );
+        assert_eq!(
+            selected(WhichJob::NotCompleted),
+            vec![
+                JobState::Pending,
+                JobState::PendingHeld,
+                JobState::Processing,
+                JobState::ProcessingStopped
+            ]
+        );
+        assert_eq!(selected(WhichJob::All), JOB_STATES.to_vec());
+    }
+
+    #[test]
+    fn single_state_keywords_select_their_state_only() {
+        let single = [
+            (WhichJob::Pending, JobState::Pending),
+            (WhichJob::PendingHeld, JobState::PendingHeld),
+            (WhichJob::Processing, JobState::Processing),
+            (WhichJob::ProcessingStopped, JobState::ProcessingStopped),
+            (WhichJob::Canceled, JobState::Canceled),
+            (WhichJob::Aborted, JobState::Aborted),
+        ];
+        for (which, state) in single {
+            assert_eq!(selected(which), vec![state], "{:?}", which);
+        }
+    }
+
+    #[test]
+    fn resolutions_compare_across_units() {
+        let dpi_600 = Resolution::new_dpi(600, 600);
+        assert!(dpi_600.approx_eq(&Resolution::new_dpcm(236, 236)));
+        assert!(Resolution::new_dpcm(236, 236).approx_eq(&dpi_600));
+        assert!(dpi_600.approx_eq(&dpi_600));
+        assert!(!dpi_600.approx_eq(&Resolution::new_dpi(300, 300)));
+        assert!(!dpi_600.approx_eq(&Resolution::new_dpi(600, 300)));
+        assert!(!dpi_600.approx_eq(&Resolution::new_dpcm(236, 118)));
+    }
+
+    #[test]
+    fn job_state_transitions_follow_rfc_8011() {
+        use JobState::*;
+        let allowed = [
+            (Pending, PendingHeld),
+            (Pending, Processing),
+            (Pending, Canceled),
+            (Pending, Aborted),
+            (PendingHeld, Pending),
+            (PendingHeld, Processing),
+            (PendingHeld, Canceled),
+            (PendingHeld, Aborted),
+            (Processing, PendingHeld),
+            (Processing, ProcessingStopped),
+            (Processing, Canceled),
+            (Processing, Aborted),
+            (Processing, Completed),
+            (ProcessingStopped, Processing),
+            (ProcessingStopped, Canceled),
+            (ProcessingStopped, Aborted),
+            (ProcessingStopped, Completed),
+            (Canceled, Pending),
+            (Aborted, Pending),
+            (Completed, Pending),
+        ];
+        for from in JOB_STATES {
+            for to in JOB_STATES {
+                assert_eq!(
+                    job_state_transition_allowed(from, to),
+                    allowed.contains(&(from, to)),
+                    "{:?} -> {:?}",
+                    from,
+                    to
+                );
+            }
+        }
+        assert!(job_state_transition_allowed(Pending, Processing));
+        assert!(!job_state_transition_allowed(Completed, Processing));
+    }
+
+    #[test]
+    fn which_job_keywords_round_trip() {
+        for which in WhichJob::ALL {
+            assert_eq!(WhichJob::try_from(which.keyword()), Ok(which));
+        }
+        assert!(WhichJob::try_from("unknown").is_err());
+    }
 }
@@ -1,18 +1,176 @@
 #![cfg(feature = "server")]
+use crate::body::Body;
+use crate::handler::{handle_ipp_via_http_with_config, IppHttpConfig};
 use crate::service::IppService;
-use crate::{body::Body, handler::handle_ipp_via_http};
-use http::{Request, Response};
+use http::header::{HeaderValue, CONNECTION};
+use http::{Request, Response, Version};
 use hyper::{
     body::Incoming,
     service::{service_fn, Service},
 };
-use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::graceful::{GracefulShutdown, Watcher};
+use read_timeout::ReadTimeout;
 use std::error::Error as StdError;
+use std::future::Future;
+use std::io;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
 #[cfg(feature = "server-tls")]
 use tokio_rustls::{rustls::ServerConfig, TlsAcceptor};
+mod read_timeout;
+#[cfg(feature = "server-tls")]
+mod tls;
+#[cfg(feature = "server-tls")]
+pub use tls::{
+    certified_key_from_reader, tls_config_from_files, tls_config_from_files_with_client_ca,
+    tls_config_from_reader, tls_config_with_sni,
+};
+
+/// Connection-level options for serving HTTP
+#[derive(Debug, Clone, Builder)]
+pub struct ServeConfig {
+    /// Abort the connection once no data has been received from the client for this long.
+    /// `None` means unlimited.
+    #[builder(default = r#"None"#)]
+    read_timeout: Option<Duration>,
+    /// Abort the connection once it has been open for this long.
+    /// `None` means unlimited.
+    #[builder(default = r#"None"#)]
+    connection_deadline: Option<Duration>,
+    /// On shutdown, also wait for in-flight jobs to reach a terminal state.
+    #[builder(default = r#"false"#)]
+    drain_jobs: bool,
+    /// On shutdown, give up waiting for open connections and in-flight jobs after this long.
+    /// `None` means unlimited.
+    #[builder(default = r#"None"#)]
+    drain_timeout: Option<Duration>,
+    /// Keep HTTP/1 connections open for further requests. Enabled by default.
+    #[builder(default = r#"true"#)]
+    keep_alive: bool,
+    /// Close HTTP/1 connections whose request headers have not been received within this long.
+    /// `None` means unlimited.
+    #[builder(default = r#"None"#)]
+    header_read_timeout: Option<Duration>,
+    /// Close HTTP/1 connections after responding to this many requests.
+    /// `None` means unlimited.
+    #[builder(default = r#"None"#)]
+    max_requests_per_connection: Option<usize>,
+    /// Stop serving and return the error once accepting connections has failed this many
+    /// times in a row with errors other than the transient ones of a single connection
+    /// (e.g. reset or aborted), such as running out of file descriptors.
+    /// `None` means unlimited.
+    #[builder(default = r#"None"#)]
+    max_consecutive_accept_errors: Option<usize>,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfigBuilder::default().build().unwrap()
+    }
+}
+
+/// Counts consecutive fatal errors of accepting connections.
+struct AcceptErrors {
+    consecutive: usize,
+    limit: Option<usize>,
+}
+
+impl AcceptErrors {
+    fn new(config: &ServeConfig) -> Self {
+        AcceptErrors {
+            consecutive: 0,
+            limit: config.max_consecutive_accept_errors,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.consecutive = 0;
+    }
+
+    /// Log a failed accept, returning the error once there have been too many in a row.
+    fn record(&mut self, err: io::Error) -> Result<(), io::Error> {
+        log::error!("Error accepting connection: {:?}", err);
+        let transient = matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::Interrupted
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::TimedOut
+        );
+        if transient {
+            self.reset();
+            return Ok(());
+        }
+        self.consecutive += 1;
+        if self.limit.is_some_and(|x| self.consecutive >= x) {
+            log::error!(
+                "Giving up after {} consecutive accept errors",
+                self.consecutive
+            );
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+async fn serve_connection<I, S, B>(stream: I, service: S, config: &ServeConfig, watcher: Watcher)
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    let stream = ReadTimeout::new(stream, config.read_timeout);
+    let mut builder = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new());
+    builder.http1().keep_alive(config.keep_alive);
+    if let Some(timeout) = config.header_read_timeout {
+        builder
+            .http1()
+            .timer(TokioTimer::new())
+            .header_read_timeout(timeout);
+    }
+    let max_requests = config.max_requests_per_connection;
+    let served = Arc::new(AtomicUsize::new(0));
+    let service = service_fn(move |req: Request<Incoming>| {
+        let served = served.fetch_add(1, Ordering::Relaxed) + 1;
+        // `Connection: close` is not allowed in HTTP/2
+        let close = req.version() <= Version::HTTP_11 && max_requests.is_some_and(|x| served >= x);
+        let response = service.call(req);
+        async move {
+            let mut response = response.await?;
+            if close {
+                response
+                    .headers_mut()
+                    .insert(CONNECTION, HeaderValue::from_static("close"));
+            }
+            Ok::<_, S::Error>(response)
+        }
+    });
+    let connection = watcher.watch(builder.serve_connection(TokioIo::new(stream), service));
+    let result = match config.connection_deadline {
+        Some(deadline) => match tokio::time::timeout(deadline, connection).await {
+            Ok(result) => result,
+            Err(_) => {
+                log::error!("Connection deadline exceeded");
+                return;
+            }
+        },
+        None => connection.await,
+    };
+    if let Err(err) = result {
+        log::error!("Error serving connection: {:?}", err);
+    }
+}
 
 /// Wrap an IPP service as a HTTP service
 pub fn wrap_as_http_service<T>(
@@ -26,9 +184,27 @@ pub fn wrap_as_http_service<T>(
 where
     T: IppService + 'static,
 {
+    wrap_as_http_service_with_config(ipp_service, IppHttpConfig::default())
+}
+
+/// Wrap an IPP service as a HTTP service with the given HTTP handling options
+pub fn wrap_as_http_service_with_config<T>(
+    ipp_service: Arc<T>,
+    config: IppHttpConfig,
+) -> impl Service<
+    Request<Incoming>,
+    Response = Response<Body>,
+    Error = anyhow::Error,
+    Future = impl futures::Future<Output = Result<Response<Body>, anyhow::Error>> + 'static,
+> + Clone
+where
+    T: IppService + 'static,
+{
+    let config = Arc::new(config);
     service_fn(move |req| {
         let ipp_service = ipp_service.clone();
-        async move { handle_ipp_via_http(req, ipp_service.as_ref()).await }
+        let config = config.clone();
+        async move { handle_ipp_via_http_with_config(req, ipp_service.as_ref(), &config).await }
     })
 }
 
@@ -41,26 +217,106 @@ where
     B: hyper::body::Body + Send + 'static,
     B::Data: Send,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    serve_http_with_config(addr, service, ServeConfig::default()).await
+}
+
+async fn drain<D: Future<Output = ()>>(graceful: GracefulShutdown, jobs: D, config: &ServeConfig) {
+    let drained = async {
+        graceful.shutdown().await;
+        if config.drain_jobs {
+            jobs.await;
+        }
+    };
+    match config.drain_timeout {
+        Some(timeout) => {
+            if tokio::time::timeout(timeout, drained).await.is_err() {
+                log::warn!("Timed out waiting for connections and jobs to finish");
+            }
+        }
+        None => drained.await,
+    }
+}
+
+/// Serve HTTP on the given address with the given connection options
+pub async fn serve_http_with_config<S, B>(
+    addr: SocketAddr,
+    service: S,
+    config: ServeConfig,
+) -> anyhow::Result<()>
+where
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    serve_http_with_shutdown(
+        addr,
+        service,
+        config,
+        futures::future::pending(),
+        futures::future::ready(()),
+    )
+    .await
+}
+
+/// Serve HTTP on the given address until `signal` resolves.  
+/// On shutdown, stop accepting connections and wait for the open ones to finish.
+/// If `drain_jobs` is set in the config, `jobs` (e.g. `SimpleIppService::wait_for_jobs()`)
+/// is awaited as well, so that in-flight jobs are not lost.
+pub async fn serve_http_with_shutdown<S, B, F, D>(
+    addr: SocketAddr,
+    service: S,
+    config: ServeConfig,
+    signal: F,
+    jobs: D,
+) -> anyhow::Result<()>
+where
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    F: Future<Output = ()>,
+    D: Future<Output = ()>,
 {
     let listener = TcpListener::bind(addr).await?;
+    let config = Arc::new(config);
+    let graceful = GracefulShutdown::new();
+    let mut signal = std::pin::pin!(signal);
+    let mut accept_errors = AcceptErrors::new(&config);
+    let mut failure = None;
     loop {
-        let stream = match listener.accept().await {
-            Ok((stream, _)) => stream,
-            Err(err) => {
-                log::error!("Error accepting connection: {:?}", err);
-                continue;
-            }
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => match accept_errors.record(err) {
+                    Ok(()) => continue,
+                    Err(err) => {
+                        failure = Some(err);
+                        break;
+                    }
+                },
+            },
+            _ = &mut signal => break,
         };
+        accept_errors.reset();
         let service = service.clone();
+        let config = config.clone();
+        let watcher = graceful.watcher();
         tokio::task::spawn(async move {
-            if let Err(err) = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
-                .serve_connection(TokioIo::new(stream), service)
-                .await
-            {
-                log::error!("Error serving connection: {:?}", err);
-            }
+            serve_connection(stream, service, &config, watcher).await;
         });
     }
+    drop(listener);
+    drain(graceful, jobs, &config).await;
+    match failure {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
 }
 
 /// Serve HTTP and HTTPS on the same port
@@ -77,67 +333,518 @@ where
     B: hyper::body::Body + Send + 'static,
     B::Data: Send,
     B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    serve_adaptive_https_with_config(addr, service, tls_config, ServeConfig::default()).await
+}
+
+/// Serve HTTP and HTTPS on the same port with the given connection options
+#[cfg(feature = "server-tls")]
+pub async fn serve_adaptive_https_with_config<S, B>(
+    addr: SocketAddr,
+    service: S,
+    tls_config: Arc<ServerConfig>,
+    config: ServeConfig,
+) -> anyhow::Result<()>
+where
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    serve_adaptive_https_with_shutdown(
+        addr,
+        service,
+        tls_config,
+        config,
+        futures::future::pending(),
+        futures::future::ready(()),
+    )
+    .await
+}
+
+/// Serve HTTP and HTTPS on the same port until `signal` resolves,
+/// draining connections and jobs as `serve_http_with_shutdown` does.
+#[cfg(feature = "server-tls")]
+pub async fn serve_adaptive_https_with_shutdown<S, B, F, D>(
+    addr: SocketAddr,
+    service: S,
+    tls_config: Arc<ServerConfig>,
+    config: ServeConfig,
+    signal: F,
+    jobs: D,
+) -> anyhow::Result<()>
+where
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    F: Future<Output = ()>,
+    D: Future<Output = ()>,
+{
+    serve_tls(addr, service, tls_config, config, true, signal, jobs).await
+}
+
+/// Serve HTTPS on the given address, without falling back to plaintext HTTP
+#[cfg(feature = "server-tls")]
+pub async fn serve_https<S, B>(
+    addr: SocketAddr,
+    service: S,
+    tls_config: Arc<ServerConfig>,
+) -> anyhow::Result<()>
+where
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    serve_https_with_config(addr, service, tls_config, ServeConfig::default()).await
+}
+
+/// Serve HTTPS on the given address with the given connection options,
+/// without falling back to plaintext HTTP
+#[cfg(feature = "server-tls")]
+pub async fn serve_https_with_config<S, B>(
+    addr: SocketAddr,
+    service: S,
+    tls_config: Arc<ServerConfig>,
+    config: ServeConfig,
+) -> anyhow::Result<()>
+where
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    serve_https_with_shutdown(
+        addr,
+        service,
+        tls_config,
+        config,
+        futures::future::pending(),
+        futures::future::ready(()),
+    )
+    .await
+}
+
+/// Serve HTTPS on the given address until `signal` resolves, without falling back to
+/// plaintext HTTP, draining connections and jobs as `serve_http_with_shutdown` does.
+#[cfg(feature = "server-tls")]
+pub async fn serve_https_with_shutdown<S, B, F, D>(
+    addr: SocketAddr,
+    service: S,
+    tls_config: Arc<ServerConfig>,
+    config: ServeConfig,
+    signal: F,
+    jobs: D,
+) -> anyhow::Result<()>
+where
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    F: Future<Output = ()>,
+    D: Future<Output = ()>,
+{
+    serve_tls(addr, service, tls_config, config, false, signal, jobs).await
+}
+
+/// The ALPN protocol negotiated on a TLS connection, e.g. `h2` or `http/1.1`.  
+/// Attached to the extensions of every request received over that connection.
+#[cfg(feature = "server-tls")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlpnProtocol(pub Vec<u8>);
+
+/// Accept TLS connections, or, if `adaptive` is set,
+/// plaintext connections whose first byte is not a TLS handshake record, until `signal` resolves.
+#[cfg(feature = "server-tls")]
+async fn serve_tls<S, B, F, D>(
+    addr: SocketAddr,
+    service: S,
+    tls_config: Arc<ServerConfig>,
+    config: ServeConfig,
+    adaptive: bool,
+    signal: F,
+    jobs: D,
+) -> anyhow::Result<()>
+where
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    F: Future<Output = ()>,
+    D: Future<Output = ()>,
 {
     let listener = TcpListener::bind(addr).await?;
     let acceptor = TlsAcceptor::from(tls_config);
+    let config = Arc::new(config);
+    let graceful = GracefulShutdown::new();
+    let mut signal = std::pin::pin!(signal);
+    let mut accept_errors = AcceptErrors::new(&config);
+    let mut failure = None;
     loop {
-        let stream = match listener.accept().await {
-            Ok((stream, _)) => stream,
-            Err(err) => {
-                log::error!("Error accepting connection: {:?}", err);
-                continue;
-            }
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => match accept_errors.record(err) {
+                    Ok(()) => continue,
+                    Err(err) => {
+                        failure = Some(err);
+                        break;
+                    }
+                },
+            },
+            _ = &mut signal => break,
         };
+        accept_errors.reset();
         let service = service.clone();
         let acceptor = acceptor.clone();
+        let config = config.clone();
+        let watcher = graceful.watcher();
         tokio::task::spawn(async move {
-            let mut header = [0u8; 1];
-            if let Err(err) = stream.peek(&mut header).await {
-                log::error!("Error peeking connection: {:?}", err);
-                return;
+            if adaptive {
+                let mut header = [0u8; 1];
+                if let Err(err) = stream.peek(&mut header).await {
+                    log::error!("Error peeking connection: {:?}", err);
+                    return;
+                }
+                if header[0] != 22 {
+                    // Not a TLS connection
+                    serve_connection(stream, service, &config, watcher).await;
+                    return;
+                }
             }
-            let result = if header[0] != 22 {
-                // Not a TLS connection
-                hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
-                    .serve_connection(TokioIo::new(stream), service)
-                    .await
-            } else {
-                let stream = match acceptor.accept(stream).await {
-                    Ok(stream) => stream,
-                    Err(err) => {
-                        log::error!("Error accepting TLS connection: {:?}", err);
-                        return;
-                    }
-                };
-                hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
-                    .serve_connection(TokioIo::new(stream), service)
-                    .await
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    log::error!("Error accepting TLS connection: {:?}", err);
+                    return;
+                }
             };
-            if let Err(err) = result {
-                log::error!("Error serving connection: {:?}", err);
+            let alpn = stream
+                .get_ref()
+                .1
+                .alpn_protocol()
+                .map(|x| AlpnProtocol(x.to_vec()));
+            match &alpn {
+                Some(AlpnProtocol(protocol)) => log::debug!(
+                    "Negotiated ALPN protocol {}",
+                    String::from_utf8_lossy(protocol)
+                ),
+                None => log::debug!("No ALPN protocol negotiated"),
             }
+            let service = service_fn(move |mut req: Request<Incoming>| {
+                if let Some(alpn) = &alpn {
+                    req.extensions_mut().insert(alpn.clone());
+                }
+                service.call(req)
+            });
+            serve_connection(stream, service, &config, watcher).await;
         });
     }
+    drop(listener);
+    drain(graceful, jobs, &config).await;
+    match failure {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
 }
 
-/// Create a TLS config from a reader of certificate and key files.  
-/// ALPN protocols are automatically set to h2, http/1.1, and http/1.0.
-#[cfg(feature = "server-tls")]
-pub fn tls_config_from_reader<R: std::io::Read>(cert: R, key: R) -> anyhow::Result<ServerConfig> {
-    use std::io::{self, BufReader};
-    let certs = rustls_pemfile::certs(&mut BufReader::new(cert))
-        .filter_map(|cert| cert.ok())
-        .collect::<Vec<_>>();
-    let key = rustls_pemfile::private_key(&mut BufReader::new(key))?;
-    let key = match key {
-        Some(x) => x,
-        None => {
-            return Err(io::Error::new(io::ErrorKind::InvalidInput, "No private key found").into())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept_errors(limit: Option<usize>) -> AcceptErrors {
+        AcceptErrors::new(
+            &ServeConfigBuilder::default()
+                .max_consecutive_accept_errors(limit)
+                .build()
+                .unwrap(),
+        )
+    }
+
+    fn fatal() -> io::Error {
+        io::Error::other("Too many open files")
+    }
+
+    #[test]
+    fn consecutive_accept_errors_trip_the_limit() {
+        let mut errors = accept_errors(Some(3));
+        assert!(errors.record(fatal()).is_ok());
+        assert!(errors.record(fatal()).is_ok());
+        assert!(errors.record(fatal()).is_err());
+    }
+
+    #[test]
+    fn accepted_connections_reset_the_count() {
+        let mut errors = accept_errors(Some(3));
+        assert!(errors.record(fatal()).is_ok());
+        assert!(errors.record(fatal()).is_ok());
+        errors.reset();
+        assert!(errors.record(fatal()).is_ok());
+        assert!(errors.record(fatal()).is_ok());
+        assert!(errors.record(fatal()).is_err());
+    }
+
+    #[test]
+    fn transient_accept_errors_are_not_counted() {
+        let mut errors = accept_errors(Some(2));
+        assert!(errors.record(fatal()).is_ok());
+        assert!(errors
+            .record(io::Error::from(io::ErrorKind::ConnectionAborted))
+            .is_ok());
+        assert!(errors.record(fatal()).is_ok());
+        assert!(errors.record(fatal()).is_err());
+    }
+
+    #[test]
+    fn accept_errors_are_unlimited_without_a_limit() {
+        let mut errors = accept_errors(None);
+        for _ in 0..100 {
+            assert!(errors.record(fatal()).is_ok());
         }
-    };
-    let mut config = ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(certs, key)?;
-    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec(), b"http/1.0".to_vec()];
-    Ok(config)
+    }
+
+    #[cfg(feature = "server-tls")]
+    mod https {
+        use super::*;
+        use crate::service::simple::{PrinterInfoBuilder, SimpleIppService};
+        use crate::service::testing::{request, RecordingHandler};
+        use ipp::model::{Operation, StatusCode};
+        use ipp::parser::IppParser;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_rustls::rustls::client::danger::{
+            HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+        };
+        use tokio_rustls::rustls::crypto::{
+            verify_tls12_signature, verify_tls13_signature, CryptoProvider,
+        };
+        use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+        use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, Error, SignatureScheme};
+        use tokio_rustls::TlsConnector;
+
+        const CERTIFICATE: &[u8] = include_bytes!("../../examples/certificate/certificate.pem");
+        const KEY: &[u8] = include_bytes!("../../examples/certificate/key.pem");
+
+        /// Trusts exactly the example certificate, which has no subject alternative name
+        /// for webpki to check the host name against
+        #[derive(Debug)]
+        struct PinnedCertificate {
+            certificate: CertificateDer<'static>,
+            provider: Arc<CryptoProvider>,
+        }
+
+        impl ServerCertVerifier for PinnedCertificate {
+            fn verify_server_cert(
+                &self,
+                end_entity: &CertificateDer<'_>,
+                _intermediates: &[CertificateDer<'_>],
+                _server_name: &ServerName<'_>,
+                _ocsp_response: &[u8],
+                _now: UnixTime,
+            ) -> Result<ServerCertVerified, Error> {
+                if *end_entity != self.certificate {
+                    return Err(Error::General("unexpected certificate".to_string()));
+                }
+                Ok(ServerCertVerified::assertion())
+            }
+
+            fn verify_tls12_signature(
+                &self,
+                message: &[u8],
+                cert: &CertificateDer<'_>,
+                dss: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, Error> {
+                let algorithms = &self.provider.signature_verification_algorithms;
+                verify_tls12_signature(message, cert, dss, algorithms)
+            }
+
+            fn verify_tls13_signature(
+                &self,
+                message: &[u8],
+                cert: &CertificateDer<'_>,
+                dss: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, Error> {
+                let algorithms = &self.provider.signature_verification_algorithms;
+                verify_tls13_signature(message, cert, dss, algorithms)
+            }
+
+            fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+                self.provider
+                    .signature_verification_algorithms
+                    .supported_schemes()
+            }
+        }
+
+        fn tls_connector() -> TlsConnector {
+            let certificate = rustls_pemfile::certs(&mut io::BufReader::new(CERTIFICATE))
+                .next()
+                .unwrap()
+                .unwrap();
+            let builder = ClientConfig::builder();
+            let verifier = PinnedCertificate {
+                certificate,
+                provider: builder.crypto_provider().clone(),
+            };
+            let config = builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+                .with_no_client_auth();
+            TlsConnector::from(Arc::new(config))
+        }
+
+        fn free_local_addr() -> SocketAddr {
+            std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+        }
+
+        fn ipp_service() -> Arc<SimpleIppService<RecordingHandler>> {
+            Arc::new(SimpleIppService::new(
+                PrinterInfoBuilder::default().build().unwrap(),
+                RecordingHandler::default(),
+            ))
+        }
+
+        fn tls_config() -> Arc<ServerConfig> {
+            Arc::new(tls_config_from_reader(CERTIFICATE, KEY).unwrap())
+        }
+
+        async fn wait_until_listening(addr: SocketAddr) {
+            for _ in 0..100 {
+                if tokio::net::TcpStream::connect(addr).await.is_ok() {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("Server did not start listening on {}", addr);
+        }
+
+        /// Start serving a `SimpleIppService` over adaptive HTTPS on a free local port
+        async fn start_adaptive_https() -> SocketAddr {
+            let addr = free_local_addr();
+            tokio::spawn(serve_adaptive_https(
+                addr,
+                wrap_as_http_service(ipp_service()),
+                tls_config(),
+            ));
+            wait_until_listening(addr).await;
+            addr
+        }
+
+        /// Send Get-Printer-Attributes over HTTP/1.1 and return the IPP status of the response
+        async fn get_printer_attributes<S>(mut stream: S) -> StatusCode
+        where
+            S: AsyncRead + AsyncWrite + Unpin,
+        {
+            let body = request(Operation::GetPrinterAttributes).to_bytes();
+            let head = format!(
+                "POST / HTTP/1.1\r\nHost: localhost:631\r\nContent-Type: application/ipp\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(&body).await.unwrap();
+            stream.flush().await.unwrap();
+            let mut response = Vec::new();
+            // Servers may close TLS connections without close_notify after `Connection: close`
+            let _ = stream.read_to_end(&mut response).await;
+            let split = response
+                .windows(4)
+                .position(|x| x == b"\r\n\r\n")
+                .expect("incomplete HTTP response");
+            let head = String::from_utf8_lossy(&response[..split]).to_ascii_lowercase();
+            assert!(head.starts_with("http/1.1 200"), "{}", head);
+            let mut body = &response[split + 4..];
+            let body = if head.contains("transfer-encoding: chunked") {
+                let mut content = Vec::new();
+                loop {
+                    let line_end = body.windows(2).position(|x| x == b"\r\n").unwrap();
+                    let size = std::str::from_utf8(&body[..line_end]).unwrap();
+                    let size = usize::from_str_radix(size.split(';').next().unwrap(), 16).unwrap();
+                    if size == 0 {
+                        break content;
+                    }
+                    content.extend_from_slice(&body[line_end + 2..line_end + 2 + size]);
+                    body = &body[line_end + 2 + size + 2..];
+                }
+            } else {
+                body.to_vec()
+            };
+            IppParser::new(io::Cursor::new(body))
+                .parse()
+                .unwrap()
+                .header()
+                .status_code()
+        }
+
+        #[tokio::test]
+        async fn adaptive_https_serves_tls_and_plaintext_on_one_port() {
+            let addr = start_adaptive_https().await;
+
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let server_name = ServerName::try_from("localhost").unwrap();
+            let stream = tls_connector().connect(server_name, stream).await.unwrap();
+            assert_eq!(
+                get_printer_attributes(stream).await,
+                StatusCode::SuccessfulOk
+            );
+
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            assert_eq!(
+                get_printer_attributes(stream).await,
+                StatusCode::SuccessfulOk
+            );
+        }
+
+        /// Shut an adaptive HTTPS server down, returning whether it waited for the jobs
+        async fn shutdown_adaptive_https(drain_jobs: bool) -> bool {
+            let addr = free_local_addr();
+            let (stop, signal) = futures::channel::oneshot::channel::<()>();
+            let drained = Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let jobs = {
+                let drained = drained.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    drained.store(true, Ordering::Relaxed);
+                }
+            };
+            let config = ServeConfigBuilder::default()
+                .drain_jobs(drain_jobs)
+                .build()
+                .unwrap();
+            let server = tokio::spawn(serve_adaptive_https_with_shutdown(
+                addr,
+                wrap_as_http_service(ipp_service()),
+                tls_config(),
+                config,
+                async move {
+                    let _ = signal.await;
+                },
+                jobs,
+            ));
+            wait_until_listening(addr).await;
+            stop.send(()).unwrap();
+            server.await.unwrap().unwrap();
+            drained.load(Ordering::Relaxed)
+        }
+
+        #[tokio::test]
+        async fn adaptive_https_drains_jobs_on_shutdown() {
+            assert!(shutdown_adaptive_https(true).await);
+            assert!(!shutdown_adaptive_https(false).await);
+        }
+    }
 }
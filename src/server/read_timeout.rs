@@ -0,0 +1,86 @@
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep, Sleep};
+
+pin_project! {
+    /// A stream that fails a read once no data has arrived for `timeout`.
+    pub(crate) struct ReadTimeout<S> {
+        #[pin]
+        inner: S,
+        timeout: Option<Duration>,
+        sleep: Option<Pin<Box<Sleep>>>,
+    }
+}
+
+impl<S> ReadTimeout<S> {
+    pub fn new(inner: S, timeout: Option<Duration>) -> Self {
+        ReadTimeout {
+            inner,
+            timeout,
+            sleep: None,
+        }
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for ReadTimeout<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+        match this.inner.poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                *this.sleep = None;
+                Poll::Ready(result)
+            }
+            Poll::Pending => {
+                if let Some(timeout) = *this.timeout {
+                    let sleep = this.sleep.get_or_insert_with(|| Box::pin(sleep(timeout)));
+                    if sleep.as_mut().poll(cx).is_ready() {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "Read timed out",
+                        )));
+                    }
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for ReadTimeout<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
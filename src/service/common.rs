@@ -1,5 +1,7 @@
-use crate::error::IppError;
+use crate::error::{IppError, IppRejection};
+use crate::model::operation_code;
 use crate::result::IppResult;
+use crate::utils::push_attribute_group;
 use anyhow;
 use http::request::Parts as ReqParts;
 use ipp::attribute::IppAttribute;
@@ -7,6 +9,31 @@ use ipp::model::{DelimiterTag, IppVersion, Operation, StatusCode};
 use ipp::request::IppRequestResponse;
 use ipp::value::IppValue;
 use num_traits::FromPrimitive;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Suggested time limit for operations that only read or validate state
+pub const READ_ONLY_OPERATION_TIMEOUT: Duration = Duration::from_secs(30);
+/// Suggested time limit for operations that carry or fetch a document
+pub const DOCUMENT_OPERATION_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// The suggested time limit for `operation`: `READ_ONLY_OPERATION_TIMEOUT` for operations
+/// that only read or validate state, `DOCUMENT_OPERATION_TIMEOUT` for those that carry
+/// or fetch a document, and none for the others.  
+/// Applied by `SimpleIppService` unless changed with `set_operation_timeout`.
+pub fn suggested_operation_timeout(operation: Operation) -> Option<Duration> {
+    match operation {
+        Operation::GetPrinterAttributes
+        | Operation::GetJobAttributes
+        | Operation::GetJobs
+        | Operation::ValidateJob => Some(READ_ONLY_OPERATION_TIMEOUT),
+        Operation::PrintJob
+        | Operation::PrintUri
+        | Operation::SendDocument
+        | Operation::SendUri => Some(DOCUMENT_OPERATION_TIMEOUT),
+        _ => None,
+    }
+}
 
 fn operation_not_supported() -> anyhow::Error {
     anyhow::Error::new(IppError {
@@ -144,6 +171,55 @@ pub trait IppService: Send + Sync {
         futures::future::ready(Err(operation_not_supported()))
     }
 
+    fn set_printer_attributes(
+        &self,
+        _head: ReqParts,
+        _req: IppRequestResponse,
+    ) -> impl futures::Future<Output = IppResult> + Send {
+        futures::future::ready(Err(operation_not_supported()))
+    }
+
+    /// Decide whether the request may be performed, before it is routed to its operation.  
+    /// `operation` is the operation code of the request, which may not be one of `Operation`,
+    /// e.g. `operation_code::SET_PRINTER_ATTRIBUTES`.
+    /// Allows everything by default; a denial is returned to the client as the error response,
+    /// typically with `client-error-not-authorized`.
+    fn authorize(
+        &self,
+        _operation: u16,
+        _head: &ReqParts,
+        _req: &IppRequestResponse,
+    ) -> impl futures::Future<Output = Result<(), IppError>> + Send {
+        futures::future::ready(Ok(()))
+    }
+
+    /// Operation attributes that requests with the operation code `operation` must carry,
+    /// checked before routing.  
+    /// Each entry is satisfied by any of its names. A missing one is reported with
+    /// `client-error-bad-request`, naming the attribute.
+    fn required_operation_attributes(&self, operation: u16) -> &'static [&'static [&'static str]] {
+        match Operation::from_u16(operation) {
+            Some(
+                Operation::SendDocument
+                | Operation::SendUri
+                | Operation::CancelJob
+                | Operation::GetJobAttributes
+                | Operation::HoldJob
+                | Operation::ReleaseJob
+                | Operation::RestartJob,
+            ) => &[&[IppAttribute::JOB_ID, IppAttribute::JOB_URI]],
+            _ => &[],
+        }
+    }
+
+    /// Time limit for performing an operation, after which `server-error-temporary-error` is returned
+    /// and the operation is dropped, see `suggested_operation_timeout`.  
+    /// `None` (the default) means unlimited. The limit is only enforced with the `server` feature
+    /// and inside a tokio runtime, as it relies on the tokio timer.
+    fn operation_timeout(&self, _operation: Operation) -> Option<Duration> {
+        None
+    }
+
     fn version(&self) -> IppVersion {
         IppVersion::v1_1()
     }
@@ -153,19 +229,52 @@ pub trait IppService: Send + Sync {
         version <= self.version().0
     }
 
+    /// Message sent in place of the details of errors that are not an `IppError`,
+    /// which may leak internals such as file paths. The details are logged instead.  
+    /// `None` (the default) sends the details to the client.
+    fn internal_error_message(&self) -> Option<String> {
+        None
+    }
+
+    /// Reject requests in which an attribute appears in more than one group of the same kind
+    /// (e.g. `document-format` in two operation attributes groups), or in both the operation
+    /// and the job attributes groups, with `client-error-bad-request`.  
+    /// Disabled by default, in which case the occurrence in the first group wins.
+    /// Repetitions within a single group cannot be detected: the parser keeps the last one.
+    fn reject_duplicate_attributes(&self) -> bool {
+        false
+    }
+
     fn build_error_response(
         &self,
         version: IppVersion,
         req_id: u32,
         error: anyhow::Error,
     ) -> IppRequestResponse {
-        let ipp_error = match error.downcast_ref::<IppError>() {
-            Some(e) => e.clone(),
-            None => IppError {
-                code: StatusCode::ServerErrorInternalError,
-                msg: error.to_string(),
-            },
-        };
+        let (ipp_error, unsupported_attributes) =
+            if let Some(rejection) = error.downcast_ref::<IppRejection>() {
+                (
+                    rejection.error.clone(),
+                    rejection.unsupported_attributes.clone(),
+                )
+            } else if let Some(e) = error.downcast_ref::<IppError>() {
+                (e.clone(), vec![])
+            } else {
+                let msg = match self.internal_error_message() {
+                    Some(msg) => {
+                        log::error!("Internal error: {:?}", error);
+                        msg
+                    }
+                    None => error.to_string(),
+                };
+                (
+                    IppError {
+                        code: StatusCode::ServerErrorInternalError,
+                        msg,
+                    },
+                    vec![],
+                )
+            };
         let mut resp = IppRequestResponse::new_response(version, ipp_error.code, req_id);
         resp.attributes_mut().add(
             DelimiterTag::OperationAttributes,
@@ -174,6 +283,16 @@ pub trait IppService: Send + Sync {
                 IppValue::TextWithoutLanguage(ipp_error.msg),
             ),
         );
+        if !unsupported_attributes.is_empty() {
+            push_attribute_group(
+                &mut resp,
+                DelimiterTag::UnsupportedAttributes,
+                unsupported_attributes
+                    .into_iter()
+                    .map(|(name, value)| IppAttribute::new(&name, value))
+                    .collect(),
+            );
+        }
         resp
     }
 
@@ -182,43 +301,318 @@ pub trait IppService: Send + Sync {
         head: ReqParts,
         req: IppRequestResponse,
     ) -> impl futures::Future<Output = IppRequestResponse> + Send {
-        async {
-            let req_id = req.header().request_id;
-            if !self.check_version(&req) {
-                return self.build_error_response(
-                    self.version(),
-                    req_id,
-                    IppError {
-                        code: StatusCode::ServerErrorVersionNotSupported,
-                        msg: StatusCode::ServerErrorVersionNotSupported.to_string(),
+        dispatch_request(self, head, req)
+    }
+}
+
+/// Check the version and route the request to the matching operation of the service.  
+/// This is the default implementation of `IppService::handle_request`,
+/// which can be reused when overriding it.
+pub async fn dispatch_request<S: IppService + ?Sized>(
+    service: &S,
+    head: ReqParts,
+    req: IppRequestResponse,
+) -> IppRequestResponse {
+    let req_id = req.header().request_id;
+    if !service.check_version(&req) {
+        return service.build_error_response(
+            service.version(),
+            req_id,
+            IppError {
+                code: StatusCode::ServerErrorVersionNotSupported,
+                msg: StatusCode::ServerErrorVersionNotSupported.to_string(),
+            }
+            .into(),
+        );
+    }
+    let version = req.header().version;
+    let operation = req.header().operation_or_status;
+    if service.reject_duplicate_attributes() {
+        if let Err(error) = check_duplicate_attributes(&req) {
+            return service.build_error_response(version, req_id, error.into());
+        }
+    }
+    // Every operation code goes through these checks, including those missing from `Operation`
+    if let Err(error) = check_required_attributes(service, operation, &req) {
+        return service.build_error_response(version, req_id, error.into());
+    }
+    if let Err(error) = service.authorize(operation, &head, &req).await {
+        return service.build_error_response(version, req_id, error.into());
+    }
+    let routed = route_request(service, operation, head, req);
+    let result = match Operation::from_u16(operation).and_then(|op| service.operation_timeout(op)) {
+        #[cfg(feature = "server")]
+        Some(timeout) if tokio::runtime::Handle::try_current().is_ok() => {
+            tokio::time::timeout(timeout, routed)
+                .await
+                .unwrap_or_else(|_| {
+                    log::warn!(
+                        "{} timed out after {:?}",
+                        crate::model::operation_name(operation),
+                        timeout
+                    );
+                    Err(IppError {
+                        code: StatusCode::ServerErrorTemporaryError,
+                        msg: "Operation timed out".to_string(),
                     }
-                    .into(),
-                );
+                    .into())
+                })
+        }
+        _ => routed.await,
+    };
+    result.unwrap_or_else(|error| service.build_error_response(version, req_id, error))
+}
+
+fn check_required_attributes<S: IppService + ?Sized>(
+    service: &S,
+    operation: u16,
+    req: &IppRequestResponse,
+) -> Result<(), IppError> {
+    let attributes = req.attributes();
+    for names in service.required_operation_attributes(operation) {
+        let present = names.iter().any(|name| {
+            attributes
+                .groups_of(DelimiterTag::OperationAttributes)
+                .any(|g| g.attributes().contains_key(*name))
+        });
+        if !present {
+            return Err(IppError {
+                code: StatusCode::ClientErrorBadRequest,
+                msg: format!(
+                    "Missing required operation attribute {}",
+                    names.join(" or ")
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn check_duplicate_attributes(req: &IppRequestResponse) -> Result<(), IppError> {
+    let mut seen = HashSet::new();
+    for group in req.attributes().groups() {
+        // Services look some attributes up in either group, so a name in both is ambiguous too
+        let kind = match group.tag() {
+            DelimiterTag::JobAttributes => DelimiterTag::OperationAttributes,
+            tag => tag,
+        };
+        for name in group.attributes().keys() {
+            if !seen.insert((kind, name.as_str())) {
+                return Err(IppError {
+                    code: StatusCode::ClientErrorBadRequest,
+                    msg: format!("Duplicate attribute {}", name),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn route_request<S: IppService + ?Sized>(
+    service: &S,
+    operation: u16,
+    head: ReqParts,
+    req: IppRequestResponse,
+) -> IppResult {
+    match Operation::from_u16(operation) {
+        Some(op) => match op {
+            Operation::PrintJob => service.print_job(head, req).await,
+            Operation::PrintUri => service.print_uri(head, req).await,
+            Operation::ValidateJob => service.validate_job(head, req).await,
+            Operation::CreateJob => service.create_job(head, req).await,
+            Operation::SendDocument => service.send_document(head, req).await,
+            Operation::SendUri => service.send_uri(head, req).await,
+            Operation::CancelJob => service.cancel_job(head, req).await,
+            Operation::GetJobAttributes => service.get_job_attributes(head, req).await,
+            Operation::GetJobs => service.get_jobs(head, req).await,
+            Operation::GetPrinterAttributes => service.get_printer_attributes(head, req).await,
+            Operation::HoldJob => service.hold_job(head, req).await,
+            Operation::ReleaseJob => service.release_job(head, req).await,
+            Operation::RestartJob => service.restart_job(head, req).await,
+            Operation::PausePrinter => service.pause_printer(head, req).await,
+            Operation::ResumePrinter => service.resume_printer(head, req).await,
+            Operation::PurgeJobs => service.purge_jobs(head, req).await,
+            _ => {
+                log::debug!("Operation {:?} ({:#06x}) is not implemented", op, operation);
+                Err(operation_not_supported())
             }
-            let version = req.header().version;
-            match Operation::from_u16(req.header().operation_or_status) {
-                Some(op) => match op {
-                    Operation::PrintJob => self.print_job(head, req).await,
-                    Operation::PrintUri => self.print_uri(head, req).await,
-                    Operation::ValidateJob => self.validate_job(head, req).await,
-                    Operation::CreateJob => self.create_job(head, req).await,
-                    Operation::SendDocument => self.send_document(head, req).await,
-                    Operation::SendUri => self.send_uri(head, req).await,
-                    Operation::CancelJob => self.cancel_job(head, req).await,
-                    Operation::GetJobAttributes => self.get_job_attributes(head, req).await,
-                    Operation::GetJobs => self.get_jobs(head, req).await,
-                    Operation::GetPrinterAttributes => self.get_printer_attributes(head, req).await,
-                    Operation::HoldJob => self.hold_job(head, req).await,
-                    Operation::ReleaseJob => self.release_job(head, req).await,
-                    Operation::RestartJob => self.restart_job(head, req).await,
-                    Operation::PausePrinter => self.pause_printer(head, req).await,
-                    Operation::ResumePrinter => self.resume_printer(head, req).await,
-                    Operation::PurgeJobs => self.purge_jobs(head, req).await,
-                    _ => Err(operation_not_supported()),
-                },
-                None => Err(operation_not_supported()),
+        },
+        None => match operation {
+            operation_code::SET_PRINTER_ATTRIBUTES => {
+                service.set_printer_attributes(head, req).await
             }
-            .unwrap_or_else(|error| self.build_error_response(version, req_id, error))
+            _ => {
+                log::debug!("Unknown operation {:#06x}", operation);
+                Err(operation_not_supported())
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::testing::{head, request_with_version};
+    use futures::executor::block_on;
+    use ipp::attribute::IppAttributeGroup;
+    use std::sync::Mutex;
+
+    /// Denies every request, recording the operation codes it was asked about
+    #[derive(Default)]
+    struct DenyAll {
+        authorized: Mutex<Vec<u16>>,
+    }
+
+    impl IppService for DenyAll {
+        async fn authorize(
+            &self,
+            operation: u16,
+            _head: &ReqParts,
+            _req: &IppRequestResponse,
+        ) -> Result<(), IppError> {
+            self.authorized.lock().unwrap().push(operation);
+            Err(IppError {
+                code: StatusCode::ClientErrorNotAuthorized,
+                msg: "Denied".to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn authorize_sees_operations_missing_from_operation() {
+        let service = DenyAll::default();
+        let mut req = request_with_version(IppVersion::v1_1(), Operation::GetPrinterAttributes);
+        req.header_mut().operation_or_status = operation_code::SET_PRINTER_ATTRIBUTES;
+        let resp = block_on(service.handle_request(head(), req));
+        assert_eq!(
+            resp.header().status_code(),
+            StatusCode::ClientErrorNotAuthorized
+        );
+        assert_eq!(
+            *service.authorized.lock().unwrap(),
+            vec![operation_code::SET_PRINTER_ATTRIBUTES]
+        );
+    }
+
+    #[test]
+    fn authorize_sees_known_operations() {
+        let service = DenyAll::default();
+        let resp = block_on(service.handle_request(
+            head(),
+            request_with_version(IppVersion::v1_1(), Operation::PurgeJobs),
+        ));
+        assert_eq!(
+            resp.header().status_code(),
+            StatusCode::ClientErrorNotAuthorized
+        );
+        assert_eq!(
+            *service.authorized.lock().unwrap(),
+            vec![Operation::PurgeJobs as u16]
+        );
+    }
+
+    /// Rejects requests with duplicate attributes, and nothing else
+    struct Strict;
+
+    impl IppService for Strict {
+        fn reject_duplicate_attributes(&self) -> bool {
+            true
+        }
+    }
+
+    fn status_with_document_formats(groups: &[DelimiterTag]) -> StatusCode {
+        let mut req = request_with_version(IppVersion::v1_1(), Operation::ValidateJob);
+        for tag in groups {
+            req.attributes_mut()
+                .groups_mut()
+                .push(IppAttributeGroup::new(*tag));
+            let group = req.attributes_mut().groups_mut().last_mut().unwrap();
+            group.attributes_mut().insert(
+                "document-format".to_string(),
+                IppAttribute::new(
+                    "document-format",
+                    IppValue::MimeMediaType("application/pdf".to_string()),
+                ),
+            );
+        }
+        block_on(Strict.handle_request(head(), req))
+            .header()
+            .status_code()
+    }
+
+    #[test]
+    fn duplicates_are_detected_across_groups() {
+        use DelimiterTag::{JobAttributes, OperationAttributes};
+        assert_eq!(
+            status_with_document_formats(&[OperationAttributes, OperationAttributes]),
+            StatusCode::ClientErrorBadRequest
+        );
+        assert_eq!(
+            status_with_document_formats(&[OperationAttributes, JobAttributes]),
+            StatusCode::ClientErrorBadRequest
+        );
+        assert_eq!(
+            status_with_document_formats(&[JobAttributes, JobAttributes]),
+            StatusCode::ClientErrorBadRequest
+        );
+        assert_eq!(
+            status_with_document_formats(&[JobAttributes]),
+            StatusCode::ServerErrorOperationNotSupported
+        );
+    }
+
+    /// Supports IPP up to the given version, and no operation at all
+    struct Versioned(IppVersion);
+
+    impl IppService for Versioned {
+        fn version(&self) -> IppVersion {
+            self.0
         }
     }
+
+    #[test]
+    fn version_negotiation_matrix() {
+        let v1_0 = IppVersion::v1_0();
+        let v1_1 = IppVersion::v1_1();
+        let v2_0 = IppVersion::v2_0();
+        let v2_1 = IppVersion::v2_1();
+        let not_supported = StatusCode::ServerErrorOperationNotSupported;
+        let version_not_supported = StatusCode::ServerErrorVersionNotSupported;
+        // (service version, request version, response version, response status)
+        let matrix = [
+            (v1_1, v1_0, v1_0, not_supported),
+            (v1_1, v1_1, v1_1, not_supported),
+            (v1_1, v2_0, v1_1, version_not_supported),
+            (v1_1, v2_1, v1_1, version_not_supported),
+            (v2_0, v1_0, v1_0, not_supported),
+            (v2_0, v1_1, v1_1, not_supported),
+            (v2_0, v2_0, v2_0, not_supported),
+            (v2_0, v2_1, v2_0, version_not_supported),
+        ];
+        for (service, version, expected_version, expected_status) in matrix {
+            let req = request_with_version(version, Operation::GetPrinterAttributes);
+            let resp = block_on(Versioned(service).handle_request(head(), req));
+            assert_eq!(
+                (resp.header().version, resp.header().status_code()),
+                (expected_version, expected_status),
+                "service {:?}, request {:?}",
+                service,
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_operations_are_not_supported() {
+        let mut req = request_with_version(IppVersion::v2_0(), Operation::GetPrinterAttributes);
+        req.header_mut().operation_or_status = 0x4001;
+        req.header_mut().request_id = 42;
+        let resp = block_on(Versioned(IppVersion::v2_0()).handle_request(head(), req));
+        assert_eq!(
+            resp.header().status_code(),
+            StatusCode::ServerErrorOperationNotSupported
+        );
+        assert_eq!(resp.header().request_id, 42);
+        assert_eq!(resp.header().version, IppVersion::v2_0());
+    }
 }
@@ -1,3 +1,10 @@
 mod common;
-pub use common::IppService;
+pub use common::{
+    dispatch_request, suggested_operation_timeout, IppService, DOCUMENT_OPERATION_TIMEOUT,
+    READ_ONLY_OPERATION_TIMEOUT,
+};
 pub mod simple;
+mod write_sink;
+pub use write_sink::WriteSink;
+#[cfg(test)]
+pub(crate) mod testing;
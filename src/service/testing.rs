@@ -0,0 +1,126 @@
+use crate::service::simple::{SimpleIppDocument, SimpleIppServiceHandler};
+use futures::io::Cursor;
+use futures::AsyncReadExt;
+use http::request::Parts as ReqParts;
+use ipp::attribute::IppAttribute;
+use ipp::model::{DelimiterTag, IppVersion, Operation};
+use ipp::payload::IppPayload;
+use ipp::request::IppRequestResponse;
+use ipp::value::IppValue;
+use std::sync::{Arc, Mutex};
+
+/// A document as received by `RecordingHandler`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RecordedDocument {
+    pub format: Option<String>,
+    pub copies: u32,
+    pub collated: bool,
+    pub content: Vec<u8>,
+}
+
+/// A handler that reads each document entirely and records it,
+/// so that tests can assert the exact payload the service delivered.
+#[derive(Clone, Default)]
+pub(crate) struct RecordingHandler {
+    pub documents: Arc<Mutex<Vec<RecordedDocument>>>,
+}
+
+impl RecordingHandler {
+    pub fn documents(&self) -> Vec<RecordedDocument> {
+        self.documents.lock().unwrap().clone()
+    }
+}
+
+impl SimpleIppServiceHandler for RecordingHandler {
+    async fn handle_document(&self, mut document: SimpleIppDocument) -> anyhow::Result<()> {
+        let mut content = Vec::new();
+        document.payload.read_to_end(&mut content).await?;
+        self.documents.lock().unwrap().push(RecordedDocument {
+            format: document.format,
+            copies: document.job_attributes.copies,
+            collated: document.job_attributes.collated_copies(),
+            content,
+        });
+        Ok(())
+    }
+}
+
+/// The head of a plain HTTP request to `localhost:631`
+pub(crate) fn head() -> ReqParts {
+    let (head, _) = http::Request::builder()
+        .uri("/")
+        .header("Host", "localhost:631")
+        .body(())
+        .unwrap()
+        .into_parts();
+    head
+}
+
+/// An IPP/2.0 request for `operation` aimed at `ipp://localhost:631/`
+pub(crate) fn request(operation: Operation) -> IppRequestResponse {
+    request_with_version(IppVersion::v2_0(), operation)
+}
+
+pub(crate) fn request_with_version(
+    version: IppVersion,
+    operation: Operation,
+) -> IppRequestResponse {
+    IppRequestResponse::new(
+        version,
+        operation,
+        Some("ipp://localhost:631/".parse().unwrap()),
+    )
+}
+
+/// A request carrying `content` as its document
+pub(crate) fn request_with_document(operation: Operation, content: &[u8]) -> IppRequestResponse {
+    let mut req = request(operation);
+    *req.payload_mut() = IppPayload::new_async(Cursor::new(content.to_vec()));
+    req
+}
+
+/// Add `name` to the first group tagged `tag`
+pub(crate) fn add_attribute(
+    req: &mut IppRequestResponse,
+    tag: DelimiterTag,
+    name: &str,
+    value: IppValue,
+) {
+    req.attributes_mut()
+        .add(tag, IppAttribute::new(name, value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::simple::{PrinterInfoBuilder, SimpleIppService};
+    use crate::service::IppService;
+    use ipp::model::StatusCode;
+
+    #[test]
+    fn records_the_exact_payload() {
+        let handler = RecordingHandler::default();
+        let service = SimpleIppService::new(
+            PrinterInfoBuilder::default().build().unwrap(),
+            handler.clone(),
+        );
+        let mut req = request_with_document(Operation::PrintJob, b"%PDF-1.7 test");
+        add_attribute(
+            &mut req,
+            DelimiterTag::OperationAttributes,
+            "document-format",
+            IppValue::MimeMediaType("application/pdf".to_string()),
+        );
+        let resp = futures::executor::block_on(service.handle_request(head(), req));
+        assert_eq!(resp.header().status_code(), StatusCode::SuccessfulOk);
+        assert_eq!(
+            handler.documents(),
+            vec![RecordedDocument {
+                format: Some("application/pdf".to_string()),
+                copies: 1,
+                collated: false,
+                content: b"%PDF-1.7 test".to_vec(),
+            }]
+        );
+    }
+}
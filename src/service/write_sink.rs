@@ -0,0 +1,56 @@
+use crate::error::IppError;
+use crate::service::simple::{SimpleIppDocument, SimpleIppServiceHandler};
+use futures::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use ipp::model::StatusCode;
+use std::future::Future;
+use std::io;
+
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A handler that copies each document into the writer opened for it by `open`,
+/// e.g. a file or a socket to a physical printer.  
+/// The document is read only as fast as the writer accepts it. Failing to open or
+/// write to the writer aborts the job with `server-error-device-error`.
+pub struct WriteSink<F> {
+    open: F,
+}
+
+impl<F> WriteSink<F> {
+    pub fn new(open: F) -> Self {
+        Self { open }
+    }
+}
+
+fn device_error(action: &str, error: io::Error) -> anyhow::Error {
+    IppError {
+        code: StatusCode::ServerErrorDeviceError,
+        msg: format!("Failed to {} the document: {}", action, error),
+    }
+    .into()
+}
+
+impl<F, Fut, W> SimpleIppServiceHandler for WriteSink<F>
+where
+    F: Fn(&SimpleIppDocument) -> Fut + Send + Sync,
+    Fut: Future<Output = io::Result<W>> + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    async fn handle_document(&self, mut document: SimpleIppDocument) -> anyhow::Result<()> {
+        let mut writer = (self.open)(&document)
+            .await
+            .map_err(|e| device_error("open a writer for", e))?;
+        let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+        loop {
+            let n = document.payload.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            writer
+                .write_all(&buffer[..n])
+                .await
+                .map_err(|e| device_error("write", e))?;
+        }
+        writer.close().await.map_err(|e| device_error("write", e))?;
+        Ok(())
+    }
+}
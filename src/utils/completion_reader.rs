@@ -0,0 +1,67 @@
+use futures::AsyncRead;
+use pin_project_lite::pin_project;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+const EOF: u8 = 1;
+const FAILED: u8 = 2;
+
+/// How a `CompletionReader` has ended so far, shared with its owner.  
+/// Neither at end of file nor failed while the reader is still in use.
+#[derive(Debug, Default)]
+pub(crate) struct ReadCompletion {
+    state: AtomicU8,
+}
+
+impl ReadCompletion {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn reached_eof(&self) -> bool {
+        self.state.load(Ordering::Relaxed) == EOF
+    }
+
+    pub fn failed(&self) -> bool {
+        self.state.load(Ordering::Relaxed) == FAILED
+    }
+}
+
+pin_project! {
+    /// A reader that records whether it reached a clean end of file or failed.
+    pub(crate) struct CompletionReader<R> {
+        #[pin]
+        reader: R,
+        completion: Arc<ReadCompletion>,
+    }
+}
+
+impl<R: AsyncRead> CompletionReader<R> {
+    pub fn new(reader: R, completion: Arc<ReadCompletion>) -> Self {
+        CompletionReader { reader, completion }
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for CompletionReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let result = this.reader.poll_read(cx, buf);
+        match &result {
+            Poll::Ready(Ok(0)) if !buf.is_empty() => {
+                this.completion.state.store(EOF, Ordering::Relaxed);
+            }
+            Poll::Ready(Err(_)) => {
+                this.completion.state.store(FAILED, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+        result
+    }
+}
@@ -0,0 +1,37 @@
+use futures::AsyncRead;
+use pin_project_lite::pin_project;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+pin_project! {
+    /// A reader that adds the number of bytes read to a shared counter.
+    pub(crate) struct CountingReader<R> {
+        #[pin]
+        reader: R,
+        counter: Arc<AtomicU64>,
+    }
+}
+
+impl<R: AsyncRead> CountingReader<R> {
+    pub fn new(reader: R, counter: Arc<AtomicU64>) -> Self {
+        CountingReader { reader, counter }
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for CountingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let result = this.reader.poll_read(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            this.counter.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        result
+    }
+}
@@ -0,0 +1,56 @@
+use futures::AsyncRead;
+use pin_project_lite::pin_project;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+
+pin_project! {
+    /// A reader that computes the SHA-256 digest of the bytes read,
+    /// storing it as lowercase hex once the end of file is reached.
+    pub(crate) struct HashingReader<R> {
+        #[pin]
+        reader: R,
+        hasher: Option<Sha256>,
+        digest: Arc<OnceLock<String>>,
+    }
+}
+
+impl<R: AsyncRead> HashingReader<R> {
+    pub fn new(reader: R, digest: Arc<OnceLock<String>>) -> Self {
+        HashingReader {
+            reader,
+            hasher: Some(Sha256::new()),
+            digest,
+        }
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for HashingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let result = this.reader.poll_read(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            if n > 0 {
+                if let Some(hasher) = this.hasher.as_mut() {
+                    hasher.update(&buf[..n]);
+                }
+            } else if !buf.is_empty() {
+                if let Some(hasher) = this.hasher.take() {
+                    let hex = hasher
+                        .finalize()
+                        .iter()
+                        .map(|x| format!("{:02x}", x))
+                        .collect();
+                    let _ = this.digest.set(hex);
+                }
+            }
+        }
+        result
+    }
+}
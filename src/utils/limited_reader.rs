@@ -0,0 +1,71 @@
+use futures::AsyncRead;
+use pin_project_lite::pin_project;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// A byte budget shared between a `LimitedReader` and its owner,
+/// so the owner can lift the limit once the limited part has been read.
+#[derive(Debug)]
+pub(crate) struct ReadLimit {
+    remaining: AtomicU64,
+    exceeded: AtomicBool,
+}
+
+impl ReadLimit {
+    pub fn new(limit: u64) -> Arc<Self> {
+        Arc::new(ReadLimit {
+            remaining: AtomicU64::new(limit),
+            exceeded: AtomicBool::new(false),
+        })
+    }
+
+    pub fn lift(&self) {
+        self.remaining.store(u64::MAX, Ordering::Relaxed);
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Relaxed)
+    }
+}
+
+pin_project! {
+    /// A reader that fails once more bytes than allowed by its `ReadLimit` are requested.
+    pub(crate) struct LimitedReader<R> {
+        #[pin]
+        reader: R,
+        limit: Arc<ReadLimit>,
+    }
+}
+
+impl<R: AsyncRead> LimitedReader<R> {
+    pub fn new(reader: R, limit: Arc<ReadLimit>) -> Self {
+        LimitedReader { reader, limit }
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for LimitedReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let remaining = this.limit.remaining.load(Ordering::Relaxed);
+        if remaining == u64::MAX {
+            return this.reader.poll_read(cx, buf);
+        }
+        if remaining == 0 && !buf.is_empty() {
+            this.limit.exceeded.store(true, Ordering::Relaxed);
+            return Poll::Ready(Err(io::Error::other("Read limit exceeded")));
+        }
+        let len = std::cmp::min(buf.len() as u64, remaining) as usize;
+        let result = this.reader.poll_read(cx, &mut buf[..len]);
+        if let Poll::Ready(Ok(n)) = result {
+            this.limit.remaining.fetch_sub(n as u64, Ordering::Relaxed);
+        }
+        result
+    }
+}
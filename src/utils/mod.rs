@@ -1,15 +1,41 @@
 use async_compression::futures::bufread;
+use futures::{io::Cursor, AsyncReadExt};
 use ipp::{
-    attribute::{IppAttribute, IppAttributes},
+    attribute::{IppAttribute, IppAttributeGroup, IppAttributes},
     model::{DelimiterTag, StatusCode},
     payload::IppPayload,
+    request::IppRequestResponse,
     value::IppValue,
 };
 use std::collections::HashSet;
+use std::io;
+mod completion_reader;
+mod counting_reader;
+mod hashing_reader;
+mod limited_reader;
 mod reader_stream;
 use crate::error::IppError;
-pub(crate) use reader_stream::ReaderStream;
+pub(crate) use completion_reader::{CompletionReader, ReadCompletion};
+pub(crate) use counting_reader::CountingReader;
+pub(crate) use hashing_reader::HashingReader;
+pub(crate) use limited_reader::{LimitedReader, ReadLimit};
+pub(crate) use reader_stream::{ReaderStream, DEFAULT_BUDGET};
 
+/// Append a group of `attributes` tagged `tag` to `resp`.
+pub fn push_attribute_group(
+    resp: &mut IppRequestResponse,
+    tag: DelimiterTag,
+    attributes: Vec<IppAttribute>,
+) {
+    let mut group = IppAttributeGroup::new(tag);
+    group
+        .attributes_mut()
+        .extend(attributes.into_iter().map(|x| (x.name().to_owned(), x)));
+    resp.attributes_mut().groups_mut().push(group);
+}
+
+/// The attribute `name` of the first group tagged `tag` that has it.  
+/// See `IppService::reject_duplicate_attributes` to refuse ambiguous requests instead.
 pub fn get_ipp_attribute<'a>(
     r: &'a IppAttributes,
     tag: DelimiterTag,
@@ -20,6 +46,8 @@ pub fn get_ipp_attribute<'a>(
         .map(|a| a.value())
 }
 
+/// Remove the attribute `name` from the first group tagged `tag` that has it,
+/// leaving any occurrence in later groups in place.
 pub fn take_ipp_attribute(
     r: &mut IppAttributes,
     tag: DelimiterTag,
@@ -32,15 +60,31 @@ pub fn take_ipp_attribute(
         .map(|a| a.into_value())
 }
 
+/// Buffer size used when decompressing documents.  
+/// Large enough to avoid many tiny reads on big print jobs.
+pub const DECOMPRESS_BUFFER_SIZE: usize = 64 * 1024;
+
 pub fn decommpress_payload(
     payload: IppPayload,
     compression: Option<&str>,
+) -> anyhow::Result<IppPayload> {
+    decommpress_payload_with_buffer(payload, compression, DECOMPRESS_BUFFER_SIZE)
+}
+
+/// Like `decommpress_payload`, reading the compressed document in chunks of `buffer_size`.
+pub fn decommpress_payload_with_buffer(
+    payload: IppPayload,
+    compression: Option<&str>,
+    buffer_size: usize,
 ) -> anyhow::Result<IppPayload> {
     match compression {
         None => Ok(payload),
         Some("none") => Ok(payload),
         Some("gzip") => {
-            let decoder = bufread::GzipDecoder::new(futures::io::BufReader::new(payload));
+            let decoder = bufread::GzipDecoder::new(futures::io::BufReader::with_capacity(
+                buffer_size,
+                payload,
+            ));
             Ok(IppPayload::new_async(decoder))
         }
         _ => Err(IppError {
@@ -51,6 +95,53 @@ pub fn decommpress_payload(
     }
 }
 
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Guess the document format from the magic bytes at the start of the document.
+pub fn detect_document_format(header: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"%PDF-", "application/pdf"),
+        (b"RaS2", "image/pwg-raster"),
+        (b"UNIRAST\0", "image/urf"),
+        (b"%!", "application/postscript"),
+        (b"\xFF\xD8\xFF", "image/jpeg"),
+        (b"\x89PNG\r\n\x1A\n", "image/png"),
+    ];
+    SIGNATURES
+        .iter()
+        .find(|(magic, _)| header.starts_with(magic))
+        .map(|(_, format)| *format)
+}
+
+/// Read up to `len` bytes from the start of the payload.
+/// The returned payload still yields the whole document.
+pub async fn peek_payload(
+    mut payload: IppPayload,
+    len: usize,
+) -> io::Result<(Vec<u8>, IppPayload)> {
+    let mut header = vec![0u8; len];
+    let mut read = 0;
+    while read < header.len() {
+        let n = payload.read(&mut header[read..]).await?;
+        if n == 0 {
+            break;
+        }
+        read += n;
+    }
+    header.truncate(read);
+    let payload = IppPayload::new_async(Cursor::new(header.clone()).chain(payload));
+    Ok((header, payload))
+}
+
+/// Read the first bytes of the payload to detect its format.
+/// The returned payload still yields the whole document.
+pub async fn sniff_document_format(
+    payload: IppPayload,
+) -> io::Result<(Option<&'static str>, IppPayload)> {
+    let (header, payload) = peek_payload(payload, 8).await?;
+    Ok((detect_document_format(&header), payload))
+}
+
 pub fn get_requested_attributes(r: &IppAttributes) -> HashSet<&str> {
     get_ipp_attribute(
         r,
@@ -65,12 +156,68 @@ pub fn get_requested_attributes(r: &IppAttributes) -> HashSet<&str> {
     .unwrap_or_else(|| HashSet::from(["all"]))
 }
 
+/// Build a `1setOf` value, or `None` if there are no values.  
+/// A single value is returned as is, which is how a one-element set is encoded anyway.
+pub fn optional_set_of(mut values: Vec<IppValue>) -> Option<IppValue> {
+    match values.len() {
+        0 => None,
+        1 => values.pop(),
+        _ => Some(IppValue::Array(values)),
+    }
+}
+
+/// Take a `name` attribute, with or without language.
+pub fn take_ipp_name(r: &mut IppAttributes, tag: DelimiterTag, name: &str) -> Option<String> {
+    take_ipp_attribute(r, tag, name).and_then(|attr| match attr {
+        IppValue::NameWithoutLanguage(name) => Some(name),
+        IppValue::NameWithLanguage { name, .. } => Some(name),
+        _ => None,
+    })
+}
+
 pub fn take_requesting_user_name(r: &mut IppAttributes) -> String {
-    take_ipp_attribute(r, DelimiterTag::OperationAttributes, "requesting-user-name")
-        .and_then(|attr| match attr {
-            IppValue::NameWithoutLanguage(name) => Some(name),
-            IppValue::NameWithLanguage { name, .. } => Some(name),
-            _ => None,
-        })
+    take_ipp_name(r, DelimiterTag::OperationAttributes, "requesting-user-name")
         .unwrap_or_else(|| "anonymous".to_string())
 }
+
+/// `requesting-user-uri`, e.g. a `mailto:` or `acct:` URI identifying the user
+pub fn take_requesting_user_uri(r: &mut IppAttributes) -> Option<String> {
+    take_ipp_attribute(r, DelimiterTag::OperationAttributes, "requesting-user-uri")
+        .and_then(|attr| attr.into_uri().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keywords(values: &[&str]) -> Vec<IppValue> {
+        values
+            .iter()
+            .map(|x| IppValue::Keyword(x.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn optional_set_of_omits_empty_sets() {
+        assert_eq!(optional_set_of(vec![]), None);
+    }
+
+    #[test]
+    fn optional_set_of_unwraps_single_values() {
+        assert_eq!(
+            optional_set_of(keywords(&["adobe-1.7"])),
+            Some(IppValue::Keyword("adobe-1.7".to_string()))
+        );
+    }
+
+    #[test]
+    fn optional_set_of_keeps_several_values_as_array() {
+        assert_eq!(
+            optional_set_of(keywords(&["adobe-1.7", "iso-32000-2_2017"])),
+            Some(IppValue::Array(keywords(&[
+                "adobe-1.7",
+                "iso-32000-2_2017"
+            ])))
+        );
+    }
+}
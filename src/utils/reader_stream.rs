@@ -8,6 +8,9 @@ use std::pin::Pin;
 use std::task::{Context, Poll};
 
 const DEFAULT_CAPACITY: usize = 4096;
+/// Number of consecutive chunks read without the reader ever being pending,
+/// after which the stream yields to the runtime once (256 KiB with the default capacity).
+pub(crate) const DEFAULT_BUDGET: usize = 64;
 
 pin_project! {
     #[derive(Debug)]
@@ -16,6 +19,8 @@ pin_project! {
         reader: Option<R>,
         buf: BytesMut,
         capacity: usize,
+        budget: Option<usize>,
+        ready_reads: usize,
     }
 }
 
@@ -51,11 +56,14 @@ fn poll_read_buf<T: AsyncRead + ?Sized, B: BufMut>(
 }
 
 impl<R: AsyncRead> ReaderStream<R> {
-    pub fn new(reader: R) -> Self {
+    /// Yield to the runtime after `budget` consecutive ready reads, or never if `None`.
+    pub fn with_budget(reader: R, budget: Option<usize>) -> Self {
         ReaderStream {
             reader: Some(reader),
             buf: BytesMut::new(),
             capacity: DEFAULT_CAPACITY,
+            budget,
+            ready_reads: 0,
         }
     }
 }
@@ -70,12 +78,22 @@ impl<R: AsyncRead> Stream for ReaderStream<R> {
             None => return Poll::Ready(None),
         };
 
+        // Give other tasks a chance to run when the reader is always ready
+        if this.budget.is_some_and(|x| *this.ready_reads >= x) {
+            *this.ready_reads = 0;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+
         if this.buf.capacity() == 0 {
             this.buf.reserve(*this.capacity);
         }
 
         match poll_read_buf(reader, cx, &mut this.buf) {
-            Poll::Pending => Poll::Pending,
+            Poll::Pending => {
+                *this.ready_reads = 0;
+                Poll::Pending
+            }
             Poll::Ready(Err(err)) => {
                 self.project().reader.set(None);
                 Poll::Ready(Some(Err(err)))
@@ -85,9 +103,42 @@ impl<R: AsyncRead> Stream for ReaderStream<R> {
                 Poll::Ready(None)
             }
             Poll::Ready(Ok(_)) => {
+                *this.ready_reads += 1;
                 let chunk = this.buf.split();
                 Poll::Ready(Some(Ok(chunk.freeze())))
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::AsyncReadExt;
+    use futures::task::noop_waker_ref;
+
+    fn poll_kinds<R: AsyncRead>(stream: Pin<&mut ReaderStream<R>>, polls: usize) -> Vec<bool> {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut stream = stream;
+        (0..polls)
+            .map(|_| stream.as_mut().poll_next(&mut cx).is_pending())
+            .collect()
+    }
+
+    #[test]
+    fn yields_once_the_budget_is_spent() {
+        let reader = futures::io::repeat(0).take(1 << 20);
+        let mut stream = std::pin::pin!(ReaderStream::with_budget(reader, Some(2)));
+        assert_eq!(
+            poll_kinds(stream.as_mut(), 6),
+            vec![false, false, true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn never_yields_without_budget() {
+        let reader = futures::io::repeat(0).take(1 << 20);
+        let mut stream = std::pin::pin!(ReaderStream::with_budget(reader, None));
+        assert_eq!(poll_kinds(stream.as_mut(), 100), vec![false; 100]);
+    }
+}